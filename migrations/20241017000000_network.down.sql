@@ -0,0 +1 @@
+ALTER TABLE wallet.account DROP COLUMN network_id;
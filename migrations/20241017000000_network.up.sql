@@ -0,0 +1 @@
+ALTER TABLE wallet.account ADD COLUMN network_id INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,2 @@
+DROP TABLE wallet.nullifier;
+DROP TABLE wallet.note;
@@ -0,0 +1,26 @@
+CREATE TABLE wallet.note (
+    address CHAR(64) NOT NULL,
+    commitment CHAR(64) NOT NULL,
+    tx_hash CHAR(64) NOT NULL,
+    position BIGINT NOT NULL,
+    sequence BIGINT NOT NULL,
+    block_hash CHAR(64) NOT NULL,
+    asset_id CHAR(64) NOT NULL,
+    value BIGINT NOT NULL,
+    memo TEXT NOT NULL,
+    sender CHAR(64) NOT NULL,
+    owner CHAR(64) NOT NULL,
+    nullifier CHAR(64),
+    spent_tx CHAR(64),
+    CONSTRAINT note_pkey PRIMARY KEY (address, commitment)
+);
+
+CREATE INDEX note_nullifier_idx ON wallet.note (nullifier);
+
+CREATE TABLE wallet.nullifier (
+    nullifier CHAR(64) NOT NULL,
+    address CHAR(64) NOT NULL,
+    tx_hash CHAR(64) NOT NULL,
+    sequence BIGINT NOT NULL,
+    CONSTRAINT nullifier_pkey PRIMARY KEY (nullifier)
+);
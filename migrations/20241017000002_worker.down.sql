@@ -0,0 +1,3 @@
+DROP TABLE wallet.worker;
+DROP INDEX wallet.account_worker_idx;
+ALTER TABLE wallet.account DROP COLUMN worker_id;
@@ -0,0 +1,11 @@
+ALTER TABLE wallet.account ADD COLUMN worker_id INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX account_worker_idx ON wallet.account (worker_id);
+
+CREATE TABLE wallet.worker (
+    id INTEGER NOT NULL,
+    head BIGINT NOT NULL,
+    accounts BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL,
+    CONSTRAINT worker_pkey PRIMARY KEY (id)
+);
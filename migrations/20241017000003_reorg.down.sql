@@ -0,0 +1,2 @@
+DROP TABLE wallet.reorg;
+DROP TABLE wallet.block;
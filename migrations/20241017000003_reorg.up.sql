@@ -0,0 +1,19 @@
+CREATE TABLE wallet.block (
+    worker_id INTEGER NOT NULL,
+    sequence BIGINT NOT NULL,
+    hash CHAR(64) NOT NULL,
+    CONSTRAINT block_pkey PRIMARY KEY (worker_id, sequence)
+);
+
+CREATE TABLE wallet.reorg (
+    id BIGSERIAL NOT NULL,
+    address CHAR(64) NOT NULL,
+    from_sequence BIGINT NOT NULL,
+    from_hash CHAR(64) NOT NULL,
+    to_sequence BIGINT NOT NULL,
+    to_hash CHAR(64) NOT NULL,
+    created_at BIGINT NOT NULL,
+    CONSTRAINT reorg_pkey PRIMARY KEY (id)
+);
+
+CREATE INDEX reorg_address_idx ON wallet.reorg (address);
@@ -0,0 +1,2 @@
+DROP TABLE wallet.balance;
+DROP TABLE wallet.transaction;
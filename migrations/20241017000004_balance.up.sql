@@ -0,0 +1,16 @@
+CREATE TABLE wallet.transaction (
+    address CHAR(64) NOT NULL,
+    hash CHAR(64) NOT NULL,
+    sequence BIGINT NOT NULL,
+    block_hash CHAR(64) NOT NULL,
+    CONSTRAINT transaction_pkey PRIMARY KEY (address, hash)
+);
+
+CREATE INDEX transaction_sequence_idx ON wallet.transaction (address, sequence);
+
+CREATE TABLE wallet.balance (
+    address CHAR(64) NOT NULL,
+    asset_id CHAR(64) NOT NULL,
+    value BIGINT NOT NULL,
+    CONSTRAINT balance_pkey PRIMARY KEY (address, asset_id)
+);
@@ -0,0 +1 @@
+ALTER TABLE wallet.note DROP COLUMN spent_sequence;
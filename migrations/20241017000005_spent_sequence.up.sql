@@ -0,0 +1,3 @@
+ALTER TABLE wallet.note ADD COLUMN spent_sequence BIGINT;
+
+UPDATE wallet.note SET spent_sequence = wallet.nullifier.sequence FROM wallet.nullifier WHERE wallet.note.nullifier = wallet.nullifier.nullifier;
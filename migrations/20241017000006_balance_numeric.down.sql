@@ -0,0 +1 @@
+ALTER TABLE wallet.balance ALTER COLUMN value TYPE BIGINT;
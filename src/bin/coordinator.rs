@@ -0,0 +1,68 @@
+use std::net::SocketAddr;
+
+use anyhow::Result;
+use clap::Parser;
+use ironfish_server::{
+    config::CoordinatorConfig, handle_signals, initialize_logger, run_coordinator,
+};
+
+#[derive(Parser, Debug, Clone)]
+pub struct Command {
+    /// The ip:port server will listen on
+    #[clap(short, long, default_value = "0.0.0.0:10003")]
+    pub listen: SocketAddr,
+    /// Base url of a prover worker, repeat for each worker
+    #[clap(long = "worker")]
+    pub workers: Vec<String>,
+    /// Circuits sent to a worker at once
+    #[clap(long, default_value = "4")]
+    pub shard_size: usize,
+    /// Other workers a failing shard is retried on
+    #[clap(long, default_value = "2")]
+    pub retries: usize,
+    /// Seconds before a shard sent to a worker times out
+    #[clap(long, default_value = "60")]
+    pub worker_timeout: u64,
+    /// Api key sent to the workers, when they run with `--auth`
+    #[clap(long)]
+    pub api_key: Option<String>,
+    /// Api key required to register workers with `POST /workers`, registration is off without it
+    #[clap(long)]
+    pub register_key: Option<String>,
+    /// Seconds between two health checks of the workers
+    #[clap(long, default_value = "10")]
+    pub health_interval: u64,
+    /// Set your logger level
+    #[clap(short, long, default_value = "0")]
+    pub verbosity: u8,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Command::parse();
+    let Command {
+        listen,
+        workers,
+        shard_size,
+        retries,
+        worker_timeout,
+        api_key,
+        register_key,
+        health_interval,
+        verbosity,
+    } = args;
+    initialize_logger(verbosity);
+    handle_signals().await?;
+    run_coordinator(CoordinatorConfig {
+        listen,
+        workers,
+        shard_size,
+        retries,
+        worker_timeout,
+        api_key,
+        register_key,
+        health_interval,
+    })
+    .await?;
+    Ok(())
+}
@@ -1,7 +1,10 @@
-use anyhow::Result;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
 use clap::Parser;
 use ironfish_server::{
     config::DbConfig,
+    constants::{Network, NetworkProfile},
     db_handler::{DBHandler, PgHandler, RedisClient, REDIS_ACCOUNT_KEY},
     handle_signals, initialize_logger,
     rpc_handler::{abi::ImportAccountReq, RpcHandler},
@@ -24,6 +27,15 @@ pub struct Command {
     /// Destination db config file
     #[clap(long)]
     pub dconfig: String,
+    /// Timeout of Ironfish rpc requests in seconds
+    #[clap(long, default_value = "5")]
+    pub rpc_timeout: u64,
+    /// Network of migrated accounts, mainnet, testnet or devnet
+    #[clap(long, default_value = "mainnet")]
+    pub network: Network,
+    /// Genesis block hash, required for testnet and devnet
+    #[clap(long)]
+    pub genesis_hash: Option<String>,
 }
 
 #[tokio::main]
@@ -35,40 +47,40 @@ async fn main() -> Result<()> {
         node,
         dname,
         dconfig,
+        rpc_timeout,
+        network,
+        genesis_hash,
     } = args;
     initialize_logger(verbosity);
     handle_signals().await?;
-    let db_config = DbConfig::load(config).unwrap();
+    let network = NetworkProfile::new(network, genesis_hash)
+        .ok_or(anyhow!("Genesis hash is required for {}", network))?;
+    if dname != "redis" && dname != "postgres" {
+        return Err(anyhow!(
+            "Unknown destination db `{}`, expected redis or postgres",
+            dname
+        ));
+    }
+    let db_config = DbConfig::load(config)?;
     let redis_handler = RedisClient::from_config(&db_config);
-    let rpc_handler = RpcHandler::new(node.into());
-    let accounts_v0 = redis_handler.hgetall(REDIS_ACCOUNT_KEY).await.unwrap();
-    match dname.as_str() {
-        "redis" => {
-            for (_, name) in accounts_v0.into_iter() {
-                if let Ok(imported) = rpc_handler.export_account(name).await {
-                    let account = imported.data.account;
-                    let account: ImportAccountReq = serde_json::from_str(&account).unwrap();
-                    let _ = redis_handler
-                        .save_account(account.to_account(), 0)
-                        .await
-                        .unwrap();
-                }
-            }
-        }
-        "postgres" => {
-            let pg_handler = PgHandler::from_config(&DbConfig::load(dconfig).unwrap());
-            for (_, name) in accounts_v0.into_iter() {
-                if let Ok(imported) = rpc_handler.export_account(name).await {
-                    let account = imported.data.account;
-                    let account: ImportAccountReq = serde_json::from_str(&account).unwrap();
-                    let _ = pg_handler
-                        .save_account(account.to_account(), 0)
-                        .await
-                        .unwrap();
-                }
-            }
+    let rpc_handler = RpcHandler::new(node.into(), Duration::from_secs(rpc_timeout));
+    let accounts_v0 = redis_handler
+        .hgetall(REDIS_ACCOUNT_KEY)
+        .await
+        .map_err(|e| anyhow!("Failed to read accounts to migrate: {}", e))?;
+    let pg_handler = match dname.as_str() {
+        "postgres" => Some(PgHandler::from_config(&DbConfig::load(dconfig)?)),
+        _ => None,
+    };
+    for (_, name) in accounts_v0.into_iter() {
+        if let Ok(imported) = rpc_handler.export_account(name).await {
+            let account: ImportAccountReq = serde_json::from_str(&imported.data.account)?;
+            let account = account.to_account(&network)?;
+            match &pg_handler {
+                Some(pg_handler) => pg_handler.save_account(account, 0).await?,
+                None => redis_handler.save_account(account, 0).await?,
+            };
         }
-        _ => unimplemented!(),
     }
     Ok(())
 }
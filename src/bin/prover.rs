@@ -2,13 +2,58 @@ use std::net::SocketAddr;
 
 use anyhow::Result;
 use clap::Parser;
-use ironfish_server::{handle_signals, initialize_logger, run_prover};
+use ironfish_server::{
+    config::{ProverAuthConfig, ProverConfig},
+    handle_signals, initialize_logger, run_prover,
+};
 
 #[derive(Parser, Debug, Clone)]
 pub struct Command {
     /// The ip:port server will listen on
     #[clap(short, long, default_value = "0.0.0.0:10002")]
     pub listen: SocketAddr,
+    /// Seconds before a synchronous proof request times out
+    #[clap(long, default_value = "30")]
+    pub request_timeout: u64,
+    /// Max proof jobs waiting for a worker
+    #[clap(long, default_value = "64")]
+    pub queue_size: usize,
+    /// Proof jobs proven concurrently
+    #[clap(long, default_value = "2")]
+    pub workers: usize,
+    /// Seconds a finished proof job is kept for polling
+    #[clap(long, default_value = "600")]
+    pub job_ttl: u64,
+    /// Verify every generated proof before returning it
+    #[clap(long)]
+    pub self_check: bool,
+    /// Threads of the proving pool, all cores when 0
+    #[clap(long, default_value = "0")]
+    pub threads: usize,
+    /// Circuits proven or verified at the same time, more are refused as busy
+    #[clap(long, default_value = "64")]
+    pub max_in_flight: usize,
+    /// Load the params at startup, `/ready` reports ready once they are loaded
+    #[clap(long)]
+    pub preload: bool,
+    /// Directory holding `sapling-{spend,output,mint}.params`, loaded at startup
+    #[clap(long)]
+    pub params_dir: Option<String>,
+    /// `sha256sum` style file the params of `--params-dir` are checked against
+    #[clap(long)]
+    pub params_checksums: Option<String>,
+    /// Proofs kept in memory by circuit hash, the cache is off when 0 and no redis is set
+    #[clap(long, default_value = "0")]
+    pub cache_size: usize,
+    /// Seconds a cached proof is reused
+    #[clap(long, default_value = "3600")]
+    pub cache_ttl: u64,
+    /// Redis url cached proofs are shared through
+    #[clap(long)]
+    pub cache_redis: Option<String>,
+    /// Path to the yaml or toml file of clients allowed to use the prover
+    #[clap(long)]
+    pub auth: Option<String>,
     /// Set your logger level
     #[clap(short, long, default_value = "0")]
     pub verbosity: u8,
@@ -17,9 +62,44 @@ pub struct Command {
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Command::parse();
-    let Command { listen, verbosity } = args;
+    let Command {
+        listen,
+        request_timeout,
+        queue_size,
+        workers,
+        job_ttl,
+        self_check,
+        threads,
+        max_in_flight,
+        preload,
+        params_dir,
+        params_checksums,
+        cache_size,
+        cache_ttl,
+        cache_redis,
+        auth,
+        verbosity,
+    } = args;
     initialize_logger(verbosity);
     handle_signals().await?;
-    run_prover(listen.into()).await?;
+    let auth = auth.map(ProverAuthConfig::load).transpose()?;
+    run_prover(ProverConfig {
+        listen,
+        request_timeout,
+        queue_size,
+        workers,
+        job_ttl,
+        self_check,
+        threads,
+        max_in_flight,
+        preload,
+        params_dir,
+        params_checksums,
+        cache_size,
+        cache_ttl,
+        cache_redis,
+        auth,
+    })
+    .await?;
     Ok(())
 }
@@ -0,0 +1,39 @@
+use anyhow::Result;
+use clap::Parser;
+use futures::future::join_all;
+use ironfish_server::{
+    config::ServerConfig,
+    db_handler::{DBHandler, PgHandler},
+    handle_signals, initialize_logger,
+    rpc_handler::RpcPool,
+    scanner::spawn_workers,
+};
+
+#[derive(Parser, Debug, Clone)]
+pub struct Command {
+    /// The path to server config file, yaml or toml
+    #[clap(short, long)]
+    pub config: String,
+    /// Set your logger level
+    #[clap(short, long, default_value = "0")]
+    pub verbosity: u8,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Command::parse();
+    let Command { config, verbosity } = args;
+    initialize_logger(verbosity);
+    handle_signals().await?;
+    let config = ServerConfig::load(config)?;
+    let db_handler = PgHandler::from_config(&config.db);
+    let rpc_pool = RpcPool::new(
+        config.node_endpoints(),
+        config.transport,
+        config.timeouts.rpc_timeout(),
+        config.health_check.max_lag,
+    );
+    rpc_pool.spawn_health_check(config.health_check.interval());
+    join_all(spawn_workers(db_handler, rpc_pool, config.scanner)).await;
+    Ok(())
+}
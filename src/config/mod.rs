@@ -1,9 +1,15 @@
 use anyhow::anyhow;
 use anyhow::Result;
+use axum::http::HeaderValue;
 use serde::{Deserialize, Serialize};
 use tracing::info;
 
-use std::{fs, path::Path};
+use std::{fs, net::SocketAddr, path::Path, time::Duration};
+
+use crate::{
+    constants::{Network, NetworkProfile, OREOSRIPTIONS_ENDPOINT},
+    rpc_handler::RpcTransportKind,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DbConfig {
@@ -55,9 +61,751 @@ impl DbConfig {
     }
 }
 
+/// Top level config of the data provider server, loaded from a yaml or toml file.
+/// Every field except `db` has a default, env vars prefixed with `OREO_` override file values.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerConfig {
+    #[serde(default = "default_listen")]
+    pub listen: SocketAddr,
+    #[serde(default = "default_node")]
+    pub node: String,
+    /// Ironfish nodes served as a pool, `node` is used when empty
+    #[serde(default)]
+    pub nodes: Vec<String>,
+    /// Transport used to reach the nodes, http, tcp or ipc
+    #[serde(default)]
+    pub transport: RpcTransportKind,
+    #[serde(default)]
+    pub health_check: HealthCheckConfig,
+    #[serde(default)]
+    pub network: Network,
+    /// Required for testnet and devnet, overrides the builtin genesis hash otherwise
+    #[serde(default)]
+    pub genesis_hash: Option<String>,
+    pub db: DbConfig,
+    #[serde(default)]
+    pub redis: Option<DbConfig>,
+    #[serde(default = "default_orescriptions_endpoint")]
+    pub orescriptions_endpoint: String,
+    #[serde(default)]
+    pub timeouts: TimeoutConfig,
+    #[serde(default)]
+    pub cors: CorsConfig,
+    #[serde(default)]
+    pub defaults: RequestDefaults,
+    #[serde(default)]
+    pub scanner: ScannerConfig,
+    #[serde(default)]
+    pub proving: ProvingConfig,
+}
+
+/// Timeouts in seconds
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeoutConfig {
+    #[serde(default = "default_request_timeout")]
+    pub request: u64,
+    #[serde(default = "default_rpc_timeout")]
+    pub rpc: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthCheckConfig {
+    /// Seconds between two health checks of every node
+    #[serde(default = "default_health_check_interval")]
+    pub interval: u64,
+    /// Max blocks a node can be behind the best node before it's ejected
+    #[serde(default = "default_max_lag")]
+    pub max_lag: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScannerConfig {
+    /// Decrypt notes server side instead of relying on the node's wallet
+    #[serde(default)]
+    pub enabled: bool,
+    /// Seconds between two scan rounds
+    #[serde(default = "default_scan_interval")]
+    pub interval: u64,
+    /// Max blocks scanned in one round
+    #[serde(default = "default_scan_batch")]
+    pub batch: u64,
+    /// Scanner workers run by this process, accounts are sharded across all live workers
+    #[serde(default = "default_scan_workers")]
+    pub workers: u32,
+    /// Id of the first worker of this process, processes sharing a db need disjoint ids
+    #[serde(default = "default_first_worker_id")]
+    pub first_worker_id: u32,
+    /// Seconds without progress after which a worker is considered gone
+    #[serde(default = "default_worker_timeout")]
+    pub worker_timeout: u64,
+}
+
+/// In process prover of `/createProvenTx`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvingConfig {
+    /// Serve `/createProvenTx`, transactions are proven by the server
+    #[serde(default)]
+    pub enabled: bool,
+    /// Threads of the proving pool, all cores when 0
+    #[serde(default)]
+    pub threads: usize,
+    /// Circuits proven at the same time, more are refused as busy
+    #[serde(default = "default_proving_max_in_flight")]
+    pub max_in_flight: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CorsConfig {
+    /// Empty list or `*` allows any origin
+    #[serde(default)]
+    pub allow_origins: Vec<String>,
+}
+
+/// Values used when a client request leaves optional parameters empty
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestDefaults {
+    #[serde(default = "default_confirmations")]
+    pub confirmations: u32,
+    #[serde(default = "default_transactions_limit")]
+    pub transactions_limit: u32,
+    #[serde(default = "default_fee")]
+    pub fee: String,
+    #[serde(default = "default_expiration_delta")]
+    pub expiration_delta: u32,
+}
+
+/// Prover settings, built from the prover cli options
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProverConfig {
+    pub listen: SocketAddr,
+    /// Seconds before a synchronous `/generateProofs` request times out
+    pub request_timeout: u64,
+    /// Max proof jobs waiting for a worker
+    pub queue_size: usize,
+    /// Proof jobs proven concurrently
+    pub workers: usize,
+    /// Seconds a finished job is kept for polling
+    pub job_ttl: u64,
+    /// Verify every generated proof before returning it
+    pub self_check: bool,
+    /// Threads of the proving pool, all cores when 0
+    pub threads: usize,
+    /// Circuits proven or verified at the same time, more are refused as busy
+    pub max_in_flight: usize,
+    /// Load the params at startup instead of on the first request
+    pub preload: bool,
+    /// Directory holding `sapling-{spend,output,mint}.params`, loaded at startup,
+    /// the params built in ironfish are used when unset
+    pub params_dir: Option<String>,
+    /// `sha256sum` style file the params of `params_dir` are checked against
+    pub params_checksums: Option<String>,
+    /// Proofs kept in memory by circuit hash, the cache is off when 0 and no redis is set
+    pub cache_size: usize,
+    /// Seconds a cached proof is reused
+    pub cache_ttl: u64,
+    /// Redis url cached proofs are shared through
+    pub cache_redis: Option<String>,
+    /// Clients allowed to use the prover, open to anyone when unset
+    pub auth: Option<ProverAuthConfig>,
+}
+
+/// Coordinator settings, built from the coordinator cli options
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoordinatorConfig {
+    pub listen: SocketAddr,
+    /// Base urls of the prover workers known at startup, more can register later
+    pub workers: Vec<String>,
+    /// Circuits sent to a worker at once
+    pub shard_size: usize,
+    /// Other workers a failing shard is retried on
+    pub retries: usize,
+    /// Seconds before a shard sent to a worker times out
+    pub worker_timeout: u64,
+    /// Api key sent to the workers, when they run with `--auth`
+    pub api_key: Option<String>,
+    /// Api key required to register workers at runtime, registration is off without it
+    pub register_key: Option<String>,
+    /// Seconds between two health checks of the workers
+    pub health_interval: u64,
+}
+
+/// Prover clients, loaded from a yaml or toml file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProverAuthConfig {
+    pub clients: Vec<ProverClient>,
+    /// Seconds a signed request timestamp may differ from the prover clock
+    #[serde(default = "default_max_skew")]
+    pub max_skew: u64,
+}
+
+/// A prover client, authenticated with `key` as api key or as hmac secret
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProverClient {
+    pub id: String,
+    pub key: String,
+    /// Requests of this client served at the same time, proof jobs count until they finish
+    #[serde(default = "default_max_concurrent")]
+    pub max_concurrent: usize,
+    /// Requests of this client accepted per minute
+    #[serde(default = "default_requests_per_minute")]
+    pub requests_per_minute: u32,
+}
+
+fn default_listen() -> SocketAddr {
+    "0.0.0.0:10001".parse().unwrap()
+}
+
+fn default_max_skew() -> u64 {
+    300
+}
+
+fn default_max_concurrent() -> usize {
+    2
+}
+
+fn default_requests_per_minute() -> u32 {
+    60
+}
+
+fn default_node() -> String {
+    "127.0.0.1:9092".to_string()
+}
+
+fn default_health_check_interval() -> u64 {
+    10
+}
+
+fn default_max_lag() -> u64 {
+    5
+}
+
+fn default_scan_interval() -> u64 {
+    10
+}
+
+fn default_scan_batch() -> u64 {
+    100
+}
+
+fn default_scan_workers() -> u32 {
+    1
+}
+
+fn default_first_worker_id() -> u32 {
+    1
+}
+
+fn default_worker_timeout() -> u64 {
+    60
+}
+
+fn default_proving_max_in_flight() -> usize {
+    16
+}
+
+fn default_orescriptions_endpoint() -> String {
+    OREOSRIPTIONS_ENDPOINT.to_string()
+}
+
+fn default_request_timeout() -> u64 {
+    30
+}
+
+fn default_rpc_timeout() -> u64 {
+    5
+}
+
+fn default_confirmations() -> u32 {
+    10
+}
+
+fn default_transactions_limit() -> u32 {
+    6
+}
+
+fn default_fee() -> String {
+    "1".to_string()
+}
+
+fn default_expiration_delta() -> u32 {
+    30
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            request: default_request_timeout(),
+            rpc: default_rpc_timeout(),
+        }
+    }
+}
+
+impl TimeoutConfig {
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request)
+    }
+
+    pub fn rpc_timeout(&self) -> Duration {
+        Duration::from_secs(self.rpc)
+    }
+}
+
+impl Default for HealthCheckConfig {
+    fn default() -> Self {
+        Self {
+            interval: default_health_check_interval(),
+            max_lag: default_max_lag(),
+        }
+    }
+}
+
+impl HealthCheckConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval)
+    }
+}
+
+impl Default for ScannerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval: default_scan_interval(),
+            batch: default_scan_batch(),
+            workers: default_scan_workers(),
+            first_worker_id: default_first_worker_id(),
+            worker_timeout: default_worker_timeout(),
+        }
+    }
+}
+
+impl ScannerConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval)
+    }
+
+    pub fn worker_ids(&self) -> Vec<u32> {
+        (0..self.workers)
+            .map_while(|i| self.first_worker_id.checked_add(i))
+            .collect()
+    }
+}
+
+impl Default for ProvingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threads: 0,
+            max_in_flight: default_proving_max_in_flight(),
+        }
+    }
+}
+
+impl ProvingConfig {
+    /// Settings of the proving pool, the other prover options don't apply to the server
+    pub fn prover_config(&self) -> ProverConfig {
+        ProverConfig {
+            threads: self.threads,
+            max_in_flight: self.max_in_flight,
+            ..Default::default()
+        }
+    }
+}
+
+impl CorsConfig {
+    pub fn allow_any(&self) -> bool {
+        self.allow_origins.is_empty() || self.allow_origins.iter().any(|x| x == "*")
+    }
+
+    pub fn origins(&self) -> Result<Vec<HeaderValue>> {
+        self.allow_origins
+            .iter()
+            .map(|origin| {
+                HeaderValue::from_str(origin)
+                    .map_err(|_| anyhow!("Invalid cors origin `{}`", origin))
+            })
+            .collect()
+    }
+}
+
+impl Default for RequestDefaults {
+    fn default() -> Self {
+        Self {
+            confirmations: default_confirmations(),
+            transactions_limit: default_transactions_limit(),
+            fee: default_fee(),
+            expiration_delta: default_expiration_delta(),
+        }
+    }
+}
+
+impl Default for ProverConfig {
+    fn default() -> Self {
+        Self {
+            listen: "0.0.0.0:10002".parse().unwrap(),
+            request_timeout: 30,
+            queue_size: 64,
+            workers: 2,
+            job_ttl: 600,
+            self_check: false,
+            threads: 0,
+            max_in_flight: 64,
+            preload: false,
+            params_dir: None,
+            params_checksums: None,
+            cache_size: 0,
+            cache_ttl: 3600,
+            cache_redis: None,
+            auth: None,
+        }
+    }
+}
+
+impl ProverConfig {
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout)
+    }
+
+    pub fn job_ttl(&self) -> Duration {
+        Duration::from_secs(self.job_ttl)
+    }
+
+    pub fn cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.cache_ttl)
+    }
+
+    pub fn cache_enabled(&self) -> bool {
+        self.cache_size > 0 || self.cache_redis.is_some()
+    }
+
+    /// Whether params are loaded before the prover reports ready
+    pub fn eager_params(&self) -> bool {
+        self.preload || self.params_dir.is_some()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.queue_size == 0 || self.workers == 0 || self.max_in_flight == 0 {
+            return Err(anyhow!(
+                "Invalid prover config: queue size, workers and max in flight must be greater than 0"
+            ));
+        }
+        if self.request_timeout == 0 {
+            return Err(anyhow!(
+                "Invalid prover config: request timeout must be greater than 0"
+            ));
+        }
+        if self.cache_enabled() && self.cache_ttl == 0 {
+            return Err(anyhow!(
+                "Invalid prover config: cache ttl must be greater than 0"
+            ));
+        }
+        if self.params_checksums.is_some() && self.params_dir.is_none() {
+            return Err(anyhow!(
+                "Invalid prover config: params checksums need a params dir"
+            ));
+        }
+        if let Some(auth) = &self.auth {
+            auth.validate()?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for CoordinatorConfig {
+    fn default() -> Self {
+        Self {
+            listen: "0.0.0.0:10003".parse().unwrap(),
+            workers: vec![],
+            shard_size: 4,
+            retries: 2,
+            worker_timeout: 60,
+            api_key: None,
+            register_key: None,
+            health_interval: 10,
+        }
+    }
+}
+
+impl CoordinatorConfig {
+    pub fn worker_timeout(&self) -> Duration {
+        Duration::from_secs(self.worker_timeout)
+    }
+
+    pub fn health_interval(&self) -> Duration {
+        Duration::from_secs(self.health_interval)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.shard_size == 0 || self.worker_timeout == 0 || self.health_interval == 0 {
+            return Err(anyhow!(
+                "Invalid coordinator config: shard size, worker timeout and health interval must be greater than 0"
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl ProverAuthConfig {
+    pub fn load(filename: impl AsRef<Path>) -> Result<Self> {
+        let path = filename.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read prover auth {}: {}", path.display(), e))?;
+        let config: Self = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&content)
+                .map_err(|e| anyhow!("Failed to parse prover auth {}: {}", path.display(), e))?,
+            _ => serde_yaml::from_str(&content)
+                .map_err(|e| anyhow!("Failed to parse prover auth {}: {}", path.display(), e))?,
+        };
+        info!(
+            "Prover auth loaded from {}, {} clients",
+            path.display(),
+            config.clients.len()
+        );
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let mut ids = std::collections::HashSet::new();
+        let mut keys = std::collections::HashSet::new();
+        for client in self.clients.iter() {
+            if client.key.is_empty()
+                || client.max_concurrent == 0
+                || client.requests_per_minute == 0
+            {
+                return Err(anyhow!(
+                    "Invalid prover auth: client {} needs a key and non zero quotas",
+                    client.id
+                ));
+            }
+            if !ids.insert(&client.id) || !keys.insert(&client.key) {
+                return Err(anyhow!(
+                    "Invalid prover auth: client {} is duplicated",
+                    client.id
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ServerConfig {
+    /// Load config from `filename`, apply env overrides and validate the result
+    pub fn load(filename: impl AsRef<Path>) -> Result<Self> {
+        let path = filename.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read server config {}: {}", path.display(), e))?;
+        let mut config = Self::parse(path, &content)?;
+        config.apply_overrides(|key| std::env::var(key).ok())?;
+        config.validate()?;
+        info!("Server config loaded from {}", path.display());
+        Ok(config)
+    }
+
+    fn parse(path: &Path, content: &str) -> Result<Self> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(content)
+                .map_err(|e| anyhow!("Failed to parse server config {}: {}", path.display(), e)),
+            _ => serde_yaml::from_str(content)
+                .map_err(|e| anyhow!("Failed to parse server config {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Override file values with `OREO_*` variables returned by `lookup`
+    pub fn apply_overrides<F>(&mut self, lookup: F) -> Result<()>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if let Some(listen) = lookup("OREO_LISTEN") {
+            self.listen = parse_override("OREO_LISTEN", &listen)?;
+        }
+        if let Some(node) = lookup("OREO_NODE") {
+            self.node = node;
+        }
+        if let Some(nodes) = lookup("OREO_NODES") {
+            self.nodes = split_list(&nodes);
+        }
+        if let Some(transport) = lookup("OREO_TRANSPORT") {
+            self.transport = parse_override("OREO_TRANSPORT", &transport)?;
+        }
+        if let Some(network) = lookup("OREO_NETWORK") {
+            self.network = parse_override("OREO_NETWORK", &network)?;
+        }
+        if let Some(hash) = lookup("OREO_GENESIS_HASH") {
+            self.genesis_hash = Some(hash);
+        }
+        if let Some(endpoint) = lookup("OREO_ORESCRIPTIONS_ENDPOINT") {
+            self.orescriptions_endpoint = endpoint;
+        }
+        if let Some(host) = lookup("OREO_DB_HOST") {
+            self.db.host = host;
+        }
+        if let Some(port) = lookup("OREO_DB_PORT") {
+            self.db.port = parse_override("OREO_DB_PORT", &port)?;
+        }
+        if let Some(user) = lookup("OREO_DB_USER") {
+            self.db.user = user;
+        }
+        if let Some(password) = lookup("OREO_DB_PASSWORD") {
+            self.db.password = password;
+        }
+        if let Some(dbname) = lookup("OREO_DB_NAME") {
+            self.db.dbname = dbname;
+        }
+        if let Some(redis) = self.redis.as_mut() {
+            if let Some(host) = lookup("OREO_REDIS_HOST") {
+                redis.host = host;
+            }
+            if let Some(port) = lookup("OREO_REDIS_PORT") {
+                redis.port = parse_override("OREO_REDIS_PORT", &port)?;
+            }
+            if let Some(password) = lookup("OREO_REDIS_PASSWORD") {
+                redis.password = password;
+            }
+        }
+        if let Some(timeout) = lookup("OREO_REQUEST_TIMEOUT") {
+            self.timeouts.request = parse_override("OREO_REQUEST_TIMEOUT", &timeout)?;
+        }
+        if let Some(timeout) = lookup("OREO_RPC_TIMEOUT") {
+            self.timeouts.rpc = parse_override("OREO_RPC_TIMEOUT", &timeout)?;
+        }
+        if let Some(enabled) = lookup("OREO_SCANNER_ENABLED") {
+            self.scanner.enabled = parse_override("OREO_SCANNER_ENABLED", &enabled)?;
+        }
+        if let Some(workers) = lookup("OREO_SCANNER_WORKERS") {
+            self.scanner.workers = parse_override("OREO_SCANNER_WORKERS", &workers)?;
+        }
+        if let Some(id) = lookup("OREO_SCANNER_FIRST_WORKER_ID") {
+            self.scanner.first_worker_id = parse_override("OREO_SCANNER_FIRST_WORKER_ID", &id)?;
+        }
+        if let Some(enabled) = lookup("OREO_PROVING_ENABLED") {
+            self.proving.enabled = parse_override("OREO_PROVING_ENABLED", &enabled)?;
+        }
+        if let Some(origins) = lookup("OREO_CORS_ORIGINS") {
+            self.cors.allow_origins = split_list(&origins);
+        }
+        Ok(())
+    }
+
+    pub fn node_endpoints(&self) -> Vec<String> {
+        if self.nodes.is_empty() {
+            vec![self.node.clone()]
+        } else {
+            self.nodes.clone()
+        }
+    }
+
+    pub fn network_profile(&self) -> Result<NetworkProfile> {
+        NetworkProfile::new(self.network, self.genesis_hash.clone()).ok_or(anyhow!(
+            "Invalid server config: `genesis_hash` is required for {}",
+            self.network
+        ))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.node_endpoints().iter().any(|node| node.is_empty()) {
+            return Err(anyhow!("Invalid server config: `node` must not be empty"));
+        }
+        if self.scanner.interval == 0 || self.scanner.batch == 0 {
+            return Err(anyhow!(
+                "Invalid server config: `scanner.interval` and `scanner.batch` must be greater than 0"
+            ));
+        }
+        if self.scanner.workers == 0 || self.scanner.first_worker_id == 0 {
+            return Err(anyhow!(
+                "Invalid server config: `scanner.workers` and `scanner.first_worker_id` must be greater than 0"
+            ));
+        }
+        // worker ids are stored as INTEGER
+        let last_worker_id = self
+            .scanner
+            .first_worker_id
+            .checked_add(self.scanner.workers - 1)
+            .and_then(|id| i32::try_from(id).ok());
+        if last_worker_id.is_none() {
+            return Err(anyhow!(
+                "Invalid server config: `scanner.first_worker_id` plus `scanner.workers` must stay below {}",
+                i32::MAX
+            ));
+        }
+        if self.scanner.worker_timeout <= self.scanner.interval {
+            return Err(anyhow!(
+                "Invalid server config: `scanner.worker_timeout` must be greater than `scanner.interval`"
+            ));
+        }
+        if self.health_check.interval == 0 {
+            return Err(anyhow!(
+                "Invalid server config: `health_check.interval` must be greater than 0"
+            ));
+        }
+        if self.proving.max_in_flight == 0 {
+            return Err(anyhow!(
+                "Invalid server config: `proving.max_in_flight` must be greater than 0"
+            ));
+        }
+        let profile = self.network_profile()?;
+        if profile.genesis_hash.len() != 64 || hex::decode(&profile.genesis_hash).is_err() {
+            return Err(anyhow!(
+                "Invalid server config: `genesis_hash` must be 32 bytes hex, got `{}`",
+                profile.genesis_hash
+            ));
+        }
+        if self.db.protocol != "postgres" {
+            return Err(anyhow!(
+                "Invalid server config: `db.protocol` must be postgres, got `{}`",
+                self.db.protocol
+            ));
+        }
+        if let Some(redis) = &self.redis {
+            if redis.protocol != "redis" {
+                return Err(anyhow!(
+                    "Invalid server config: `redis.protocol` must be redis, got `{}`",
+                    redis.protocol
+                ));
+            }
+        }
+        if !self.orescriptions_endpoint.starts_with("http://")
+            && !self.orescriptions_endpoint.starts_with("https://")
+        {
+            return Err(anyhow!(
+                "Invalid server config: `orescriptions_endpoint` must be an http(s) url, got `{}`",
+                self.orescriptions_endpoint
+            ));
+        }
+        if self.timeouts.request == 0 || self.timeouts.rpc == 0 {
+            return Err(anyhow!(
+                "Invalid server config: timeouts must be greater than 0"
+            ));
+        }
+        if !self.cors.allow_any() {
+            self.cors.origins()?;
+        }
+        Ok(())
+    }
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty())
+        .collect()
+}
+
+fn parse_override<T: std::str::FromStr>(key: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| anyhow!("Invalid value `{}` for env {}", value, key))
+}
+
 #[cfg(test)]
 mod tests {
-    use super::DbConfig;
+    use std::collections::HashMap;
+
+    use crate::{
+        constants::{Network, MAINNET_GENESIS_HASH},
+        rpc_handler::RpcTransportKind,
+    };
+
+    use super::{DbConfig, ServerConfig};
 
     #[test]
     fn redis_config_should_be_loaded() {
@@ -93,4 +841,107 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn server_config_should_be_loaded() {
+        let config = ServerConfig::load("./fixtures/server-config.yml").unwrap();
+        assert_eq!(config.listen, "0.0.0.0:10001".parse().unwrap());
+        assert_eq!(config.node, "127.0.0.1:9092");
+        assert_eq!(config.node_endpoints(), vec!["127.0.0.1:9092".to_string()]);
+        assert_eq!(config.health_check.max_lag, 5);
+        assert_eq!(config.transport, RpcTransportKind::Http);
+        assert_eq!(config.network, Network::Mainnet);
+        assert_eq!(
+            config.network_profile().unwrap().genesis_hash,
+            MAINNET_GENESIS_HASH
+        );
+        assert_eq!(config.db.protocol, "postgres");
+        assert_eq!(config.redis.unwrap().port, 6379);
+        assert_eq!(config.timeouts.request, 30);
+        assert_eq!(config.timeouts.rpc, 5);
+        assert_eq!(config.defaults.confirmations, 10);
+        assert!(!config.scanner.enabled);
+        assert_eq!(config.scanner.batch, 100);
+        assert_eq!(config.scanner.worker_ids(), vec![1]);
+        assert!(!config.proving.enabled);
+        assert_eq!(config.proving.max_in_flight, 16);
+        assert!(config.cors.allow_any());
+    }
+
+    #[test]
+    fn toml_server_config_should_be_loaded() {
+        let yaml = ServerConfig::load("./fixtures/server-config.yml").unwrap();
+        let toml = ServerConfig::load("./fixtures/server-config.toml").unwrap();
+        assert_eq!(yaml, toml);
+    }
+
+    #[test]
+    fn env_overrides_should_work() {
+        let mut config = ServerConfig::load("./fixtures/server-config.yml").unwrap();
+        let env = HashMap::from([
+            ("OREO_NODE", "10.0.0.1:9092"),
+            ("OREO_NODES", "10.0.0.1:9092,10.0.0.2:9092"),
+            ("OREO_DB_PORT", "5433"),
+            ("OREO_CORS_ORIGINS", "https://a.com, https://b.com"),
+            ("OREO_SCANNER_WORKERS", "3"),
+            ("OREO_SCANNER_FIRST_WORKER_ID", "4"),
+            ("OREO_TRANSPORT", "TCP"),
+            ("OREO_PROVING_ENABLED", "true"),
+        ]);
+        config
+            .apply_overrides(|key| env.get(key).map(|x| x.to_string()))
+            .unwrap();
+        assert_eq!(config.node, "10.0.0.1:9092");
+        assert_eq!(config.node_endpoints().len(), 2);
+        assert_eq!(config.db.port, 5433);
+        assert_eq!(config.cors.allow_origins.len(), 2);
+        assert_eq!(config.scanner.worker_ids(), vec![4, 5, 6]);
+        assert_eq!(config.transport, RpcTransportKind::Tcp);
+        assert!(config.proving.enabled);
+        assert!(config.validate().is_ok());
+
+        let env = HashMap::from([("OREO_DB_PORT", "not-a-port")]);
+        assert!(config
+            .apply_overrides(|key| env.get(key).map(|x| x.to_string()))
+            .is_err());
+        let env = HashMap::from([("OREO_TRANSPORT", "udp")]);
+        let error = config
+            .apply_overrides(|key| env.get(key).map(|x| x.to_string()))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid value `udp` for env OREO_TRANSPORT"
+        );
+    }
+
+    #[test]
+    fn invalid_server_config_should_fail() {
+        let mut config = ServerConfig::load("./fixtures/server-config.yml").unwrap();
+        config.timeouts.rpc = 0;
+        assert!(config.validate().is_err());
+        config.timeouts.rpc = 5;
+        config.db.protocol = "redis".to_string();
+        assert!(config.validate().is_err());
+        config.db.protocol = "postgres".to_string();
+        config.scanner.first_worker_id = 0;
+        assert!(config.validate().is_err());
+        config.scanner.first_worker_id = i32::MAX as u32;
+        assert!(config.validate().is_ok());
+        config.scanner.workers = 2;
+        assert!(config.validate().is_err());
+        config.scanner.first_worker_id = u32::MAX;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn testnet_requires_genesis_hash() {
+        let mut config = ServerConfig::load("./fixtures/server-config.yml").unwrap();
+        config.network = Network::Testnet;
+        assert!(config.validate().is_err());
+        config.genesis_hash = Some("zz".repeat(32));
+        assert!(config.validate().is_err());
+        config.genesis_hash = Some("00".repeat(32));
+        assert!(config.validate().is_ok());
+        assert_eq!(config.network_profile().unwrap().network.id(), 0);
+    }
 }
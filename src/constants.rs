@@ -1,3 +1,9 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::OreoError;
+
 pub const ACCOUNT_VERSION: u8 = 2;
 pub const IRON_NATIVE_ASSET: &str =
     "51f33a2f14f92735e562dc658a5639279ddca3d5079a6d1242b2a588a9cbf44c";
@@ -6,3 +12,111 @@ pub const OREOSRIPTIONS_ENDPOINT: &str = "http://localhost:20001/api";
 pub const MAINNET_GENESIS_HASH: &str =
     "eac623b099b8081d2bde92d43a4a7795385c94e2c0ae4097ef488972e83ff2b3";
 pub const MAINNET_GENESIS_SEQUENCE: i64 = 1;
+pub const TESTNET_GENESIS_SEQUENCE: i64 = 1;
+pub const DEVNET_GENESIS_SEQUENCE: i64 = 1;
+
+/// Ironfish networks, ids follow the node's `networkId`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    Testnet,
+    #[default]
+    Mainnet,
+    Devnet,
+}
+
+impl Network {
+    pub fn id(&self) -> i32 {
+        match self {
+            Network::Testnet => 0,
+            Network::Mainnet => 1,
+            Network::Devnet => 2,
+        }
+    }
+
+    pub fn from_id(id: i32) -> Option<Self> {
+        match id {
+            0 => Some(Network::Testnet),
+            1 => Some(Network::Mainnet),
+            2 => Some(Network::Devnet),
+            _ => None,
+        }
+    }
+
+    /// Builtin genesis hash, testnet and devnet genesis must be configured explicitly
+    pub fn genesis_hash(&self) -> Option<&'static str> {
+        match self {
+            Network::Mainnet => Some(MAINNET_GENESIS_HASH),
+            Network::Testnet | Network::Devnet => None,
+        }
+    }
+
+    pub fn genesis_sequence(&self) -> i64 {
+        match self {
+            Network::Testnet => TESTNET_GENESIS_SEQUENCE,
+            Network::Mainnet => MAINNET_GENESIS_SEQUENCE,
+            Network::Devnet => DEVNET_GENESIS_SEQUENCE,
+        }
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Network::Testnet => write!(f, "testnet"),
+            Network::Mainnet => write!(f, "mainnet"),
+            Network::Devnet => write!(f, "devnet"),
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "testnet" => Ok(Network::Testnet),
+            "mainnet" => Ok(Network::Mainnet),
+            "devnet" => Ok(Network::Devnet),
+            _ => Err(format!("Unknown network `{}`", s)),
+        }
+    }
+}
+
+/// Chain constants of the network this server runs against
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkProfile {
+    pub network: Network,
+    pub genesis_hash: String,
+    pub genesis_sequence: i64,
+    pub native_asset: String,
+}
+
+impl NetworkProfile {
+    pub fn new(network: Network, genesis_hash: Option<String>) -> Option<Self> {
+        let genesis_hash = genesis_hash.or(network.genesis_hash().map(|x| x.to_string()))?;
+        Some(Self {
+            network,
+            genesis_hash,
+            genesis_sequence: network.genesis_sequence(),
+            native_asset: IRON_NATIVE_ASSET.to_string(),
+        })
+    }
+
+    pub fn mainnet() -> Self {
+        Self::new(Network::Mainnet, None).unwrap()
+    }
+
+    pub fn is_genesis(&self, sequence: i64) -> bool {
+        sequence == self.genesis_sequence
+    }
+
+    /// Refuse an account created at the genesis sequence with the genesis hash of another
+    /// network, later blocks are checked against the node
+    pub fn check_created_at(&self, sequence: u64, hash: &str) -> Result<(), OreoError> {
+        if self.is_genesis(sequence as i64) && hash != self.genesis_hash {
+            return Err(OreoError::NetworkMismatch(hash.to_string()));
+        }
+        Ok(())
+    }
+}
@@ -4,21 +4,52 @@ mod redis_handler;
 pub use pg_handler::*;
 pub use redis_handler::*;
 
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
-use crate::{config::DbConfig, error::OreoError};
+use crate::{config::DbConfig, constants::Network, error::OreoError};
 
 #[async_trait::async_trait]
 pub trait DBHandler {
     /// Initialize a DB handler
     fn from_config(config: &DbConfig) -> Self;
-    /// Save account in db and return account name
+    /// Save account in db and return account name, `worker_id` is the scanner worker owning
+    /// the account and 0 leaves it unassigned until the scanners rebalance
     async fn save_account(&self, account: Account, worker_id: u32) -> Result<String, OreoError>;
     /// Get account name from db
     async fn get_account(&self, address: String) -> Result<Account, OreoError>;
     /// Remove account from db
     async fn remove_account(&self, address: String) -> Result<String, OreoError>;
+    /// Save decrypted notes, notes already saved are ignored
+    async fn save_notes(&self, notes: Vec<DecryptedNote>) -> Result<(), OreoError>;
+    /// Mark notes revealed by `spends` as spent
+    async fn mark_spent(&self, spends: Vec<SpentNullifier>) -> Result<(), OreoError>;
+    /// Unspent notes owned by account, of one asset or all assets
+    async fn get_unspent_notes(
+        &self,
+        address: String,
+        asset_id: Option<String>,
+    ) -> Result<Vec<DecryptedNote>, OreoError>;
+    /// Latest transactions touching account notes
+    async fn get_transactions(
+        &self,
+        address: String,
+        limit: i64,
+    ) -> Result<Vec<AccountTransaction>, OreoError>;
+    /// Sum of unspent notes per asset
+    async fn get_balances(&self, address: String) -> Result<Vec<AssetBalance>, OreoError>;
+    /// Owned notes not spent at or below `spent_after`
+    async fn get_owned_notes(
+        &self,
+        address: String,
+        spent_after: i64,
+    ) -> Result<Vec<DecryptedNote>, OreoError>;
+    /// Progress of the scanner workers, empty on handlers the scanner doesn't run on
+    async fn get_worker_statuses(&self) -> Result<Vec<WorkerStatus>, OreoError>;
+    /// Reorg events of an account with id greater than `since`
+    async fn get_reorgs(&self, address: String, since: i64) -> Result<Vec<ReorgEvent>, OreoError>;
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow)]
@@ -33,4 +64,204 @@ pub struct Account {
     pub out_vk: String,
     pub vk: String,
     pub address: String,
+    #[serde(default = "default_network_id")]
+    pub network_id: i32,
+    #[serde(default)]
+    pub worker_id: i32,
+}
+
+fn default_network_id() -> i32 {
+    Network::Mainnet.id()
+}
+
+/// Note decrypted by the scanner, owned by `address` when decrypted with its incoming view key
+/// or sent by `address` when decrypted with its outgoing view key
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct DecryptedNote {
+    pub address: String,
+    pub commitment: String,
+    pub tx_hash: String,
+    pub position: i64,
+    pub sequence: i64,
+    pub block_hash: String,
+    pub asset_id: String,
+    pub value: i64,
+    pub memo: String,
+    pub sender: String,
+    pub owner: String,
+    pub nullifier: Option<String>,
+    pub spent_tx: Option<String>,
+    pub spent_sequence: Option<i64>,
+}
+
+/// Transaction which created or spent notes of `address`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountTransaction {
+    pub address: String,
+    pub hash: String,
+    pub sequence: i64,
+    pub block_hash: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetBalance {
+    pub address: String,
+    pub asset_id: String,
+    /// Sum of unspent notes, as a string like the node since it may not fit in an i64
+    pub value: String,
+}
+
+impl DecryptedNote {
+    /// Notes decrypted with the outgoing view key belong to the receiver
+    pub fn is_owned(&self) -> bool {
+        self.nullifier.is_some()
+    }
+
+    pub fn is_unspent(&self) -> bool {
+        self.is_owned() && self.spent_tx.is_none()
+    }
+}
+
+/// Balance of one asset computed from stored notes, same semantics as `wallet/getBalances`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LocalBalance {
+    pub asset_id: String,
+    /// Unspent as of `head - confirmations`
+    pub confirmed: i128,
+    /// Unspent as of `head`
+    pub unconfirmed: i128,
+    /// Unconfirmed plus pending transactions, which are not tracked locally
+    pub pending: i128,
+    /// Confirmed notes not spent yet
+    pub available: i128,
+}
+
+/// Compute balances of `notes` at account `head`, `native_asset` is always reported
+pub fn local_balances(
+    notes: &[DecryptedNote],
+    head: i64,
+    confirmations: i64,
+    native_asset: &str,
+) -> Vec<LocalBalance> {
+    let confirmed_head = head - confirmations;
+    let mut balances = BTreeMap::new();
+    balances.insert(native_asset.to_string(), LocalBalance::default());
+    for note in notes
+        .iter()
+        .filter(|note| note.is_owned() && note.sequence <= head)
+    {
+        let balance = balances.entry(note.asset_id.clone()).or_default();
+        let spent_at = note.spent_sequence.unwrap_or(i64::MAX);
+        if spent_at > head {
+            balance.unconfirmed += note.value as i128;
+        }
+        if note.sequence <= confirmed_head {
+            if spent_at > confirmed_head {
+                balance.confirmed += note.value as i128;
+            }
+            if note.spent_tx.is_none() {
+                balance.available += note.value as i128;
+            }
+        }
+    }
+    balances
+        .into_iter()
+        .map(|(asset_id, balance)| LocalBalance {
+            asset_id,
+            pending: balance.unconfirmed,
+            ..balance
+        })
+        .collect()
+}
+
+/// Progress reported by a scanner worker
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkerStatus {
+    pub id: i32,
+    pub head: i64,
+    pub accounts: i64,
+    pub updated_at: i64,
+}
+
+/// Account rolled back from a block which left the canonical chain
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ReorgEvent {
+    pub id: i64,
+    pub address: String,
+    pub from_sequence: i64,
+    pub from_hash: String,
+    pub to_sequence: i64,
+    pub to_hash: String,
+    pub created_at: i64,
+}
+
+/// Nullifier revealed by a spend on chain
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SpentNullifier {
+    pub nullifier: String,
+    pub tx_hash: String,
+    pub sequence: i64,
+    pub block_hash: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{local_balances, DecryptedNote};
+
+    fn note(commitment: &str, sequence: i64, value: i64, spent: Option<i64>) -> DecryptedNote {
+        DecryptedNote {
+            address: "aa".repeat(32),
+            commitment: commitment.to_string(),
+            tx_hash: "bb".repeat(32),
+            position: 0,
+            sequence,
+            block_hash: "cc".repeat(32),
+            asset_id: "dd".repeat(32),
+            value,
+            memo: "".to_string(),
+            sender: "aa".repeat(32),
+            owner: "aa".repeat(32),
+            nullifier: Some(commitment.to_string()),
+            spent_tx: spent.map(|_| "ee".repeat(32)),
+            spent_sequence: spent,
+        }
+    }
+
+    #[test]
+    fn local_balances_should_follow_confirmations() {
+        let mut outgoing = note("04", 1, 1000, None);
+        outgoing.nullifier = None;
+        let notes = vec![
+            // confirmed and unspent
+            note("01", 5, 100, None),
+            // received in an unconfirmed block
+            note("02", 19, 10, None),
+            // confirmed, spent in an unconfirmed block
+            note("03", 2, 50, Some(18)),
+            outgoing,
+        ];
+        let balances = local_balances(&notes, 20, 10, "ff");
+        assert_eq!(balances.len(), 2);
+        let balance = &balances[0];
+        assert_eq!(balance.asset_id, "dd".repeat(32));
+        assert_eq!(balance.unconfirmed, 110);
+        assert_eq!(balance.pending, 110);
+        assert_eq!(balance.confirmed, 150);
+        assert_eq!(balance.available, 100);
+        assert_eq!(balances[1].asset_id, "ff");
+        assert_eq!(balances[1].confirmed, 0);
+    }
+
+    #[test]
+    fn local_balances_should_not_overflow() {
+        let notes = vec![note("01", 1, i64::MAX, None), note("02", 1, i64::MAX, None)];
+        let balances = local_balances(&notes, 20, 10, "ff");
+        assert_eq!(balances[0].confirmed, 2 * i64::MAX as i128);
+    }
 }
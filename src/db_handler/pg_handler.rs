@@ -1,9 +1,12 @@
 use futures::executor::block_on;
-use sqlx::{postgres::PgPoolOptions, PgPool, Row};
+use sqlx::{postgres::PgPoolOptions, PgPool, Postgres, Row, Transaction};
 
 use crate::error::OreoError;
 
-use super::{Account, DBHandler};
+use super::{
+    Account, AccountTransaction, AssetBalance, DBHandler, DecryptedNote, ReorgEvent,
+    SpentNullifier, WorkerStatus,
+};
 
 #[derive(Debug, Clone)]
 pub struct PgHandler {
@@ -17,7 +20,7 @@ impl PgHandler {
 
     pub async fn insert(&self, account: Account) -> Result<String, sqlx::Error> {
         let result = sqlx::query(
-            "INSERT INTO wallet.account (name, create_head, create_hash, head, hash, in_vk, out_vk, vk, address) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING address"
+            "INSERT INTO wallet.account (name, create_head, create_hash, head, hash, in_vk, out_vk, vk, address, network_id, worker_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) RETURNING address"
         )
         .bind(account.name.clone())
         .bind(account.create_head.clone())
@@ -28,6 +31,8 @@ impl PgHandler {
         .bind(account.out_vk.clone())
         .bind(account.vk.clone())
         .bind(account.address.clone())
+        .bind(account.network_id)
+        .bind(account.worker_id)
         .fetch_one(&self.pool)
         .await?.get(0);
         Ok(result)
@@ -55,6 +60,215 @@ impl PgHandler {
         Ok(result)
     }
 
+    pub async fn get_all(&self) -> Result<Vec<Account>, sqlx::Error> {
+        sqlx::query_as::<_, Account>("SELECT * FROM wallet.account")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn get_by_worker(&self, worker_id: i32) -> Result<Vec<Account>, sqlx::Error> {
+        sqlx::query_as::<_, Account>("SELECT * FROM wallet.account WHERE worker_id = $1")
+            .bind(worker_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn get_assignments(&self) -> Result<Vec<(String, i32)>, sqlx::Error> {
+        sqlx::query_as::<_, (String, i32)>("SELECT address, worker_id FROM wallet.account")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn assign_worker(&self, address: String, worker_id: i32) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE wallet.account SET worker_id = $1 WHERE address = $2")
+            .bind(worker_id)
+            .bind(address)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Record progress of a scanner worker, `updated_at` in unix seconds
+    pub async fn heartbeat(&self, status: WorkerStatus) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO wallet.worker (id, head, accounts, updated_at) VALUES ($1, $2, $3, $4) ON CONFLICT (id) DO UPDATE SET head = $2, accounts = $3, updated_at = $4",
+        )
+        .bind(status.id)
+        .bind(status.head)
+        .bind(status.accounts)
+        .bind(status.updated_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_workers(&self) -> Result<Vec<WorkerStatus>, sqlx::Error> {
+        sqlx::query_as::<_, WorkerStatus>("SELECT * FROM wallet.worker ORDER BY id")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Remove workers which didn't report since `before`, returns the number of removed rows
+    pub async fn prune_workers(&self, before: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM wallet.worker WHERE updated_at < $1")
+            .bind(before)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Ids of workers which reported after `since`
+    pub async fn get_live_workers(&self, since: i64) -> Result<Vec<i32>, sqlx::Error> {
+        sqlx::query_as::<_, (i32,)>(
+            "SELECT id FROM wallet.worker WHERE updated_at >= $1 ORDER BY id",
+        )
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await
+        .map(|rows| rows.into_iter().map(|row| row.0).collect())
+    }
+
+    pub async fn get_notes(&self, address: String) -> Result<Vec<DecryptedNote>, sqlx::Error> {
+        sqlx::query_as::<_, DecryptedNote>(
+            "SELECT * FROM wallet.note WHERE address = $1 ORDER BY position",
+        )
+        .bind(address)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Store what the scanner found in one block and move `addresses` to it, all or nothing
+    pub async fn save_scanned_block(
+        &self,
+        worker_id: i32,
+        sequence: i64,
+        hash: String,
+        addresses: &[String],
+        notes: &[DecryptedNote],
+        spends: &[SpentNullifier],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        insert_notes(&mut tx, notes).await?;
+        spend_notes(&mut tx, spends).await?;
+        sqlx::query(
+            "INSERT INTO wallet.block (worker_id, sequence, hash) VALUES ($1, $2, $3) ON CONFLICT (worker_id, sequence) DO UPDATE SET hash = $3",
+        )
+        .bind(worker_id)
+        .bind(sequence)
+        .bind(hash.clone())
+        .execute(&mut tx)
+        .await?;
+        for address in addresses.iter() {
+            sqlx::query(
+                "UPDATE wallet.account SET head = $1, hash = $2 WHERE address = $3 AND worker_id = $4",
+            )
+            .bind(sequence)
+            .bind(hash.clone())
+            .bind(address.clone())
+            .bind(worker_id)
+            .execute(&mut tx)
+            .await?;
+        }
+        let mut touched: Vec<String> = notes.iter().map(|note| note.address.clone()).collect();
+        touched.extend(addresses.iter().cloned());
+        refresh_balances(&mut tx, &touched).await?;
+        tx.commit().await
+    }
+
+    /// Hash of a block scanned before by a worker, used to find the fork point of a reorg
+    pub async fn get_block_hash(
+        &self,
+        worker_id: i32,
+        sequence: i64,
+    ) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_as::<_, (String,)>(
+            "SELECT hash FROM wallet.block WHERE worker_id = $1 AND sequence = $2",
+        )
+        .bind(worker_id)
+        .bind(sequence)
+        .fetch_optional(&self.pool)
+        .await
+        .map(|row| row.map(|row| row.0))
+    }
+
+    /// Roll accounts above `sequence` back to the fork point `hash`, dropping notes, spends and
+    /// transactions recorded above it and recording a reorg event for every account moved
+    pub async fn rollback(
+        &self,
+        worker_id: i32,
+        addresses: &[String],
+        sequence: i64,
+        hash: String,
+        created_at: i64,
+    ) -> Result<Vec<ReorgEvent>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM wallet.note WHERE address = ANY($1) AND sequence > $2")
+            .bind(addresses)
+            .bind(sequence)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query(
+            "UPDATE wallet.note SET spent_tx = NULL, spent_sequence = NULL FROM wallet.nullifier WHERE wallet.note.nullifier = wallet.nullifier.nullifier AND wallet.nullifier.address = ANY($1) AND wallet.nullifier.sequence > $2",
+        )
+        .bind(addresses)
+        .bind(sequence)
+        .execute(&mut tx)
+        .await?;
+        sqlx::query("DELETE FROM wallet.nullifier WHERE address = ANY($1) AND sequence > $2")
+            .bind(addresses)
+            .bind(sequence)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query("DELETE FROM wallet.transaction WHERE address = ANY($1) AND sequence > $2")
+            .bind(addresses)
+            .bind(sequence)
+            .execute(&mut tx)
+            .await?;
+        refresh_balances(&mut tx, addresses).await?;
+        let events = sqlx::query_as::<_, ReorgEvent>(
+            "INSERT INTO wallet.reorg (address, from_sequence, from_hash, to_sequence, to_hash, created_at) SELECT address, head, hash, $2, $3, $4 FROM wallet.account WHERE address = ANY($1) AND (head > $2 OR (head = $2 AND hash <> $3)) AND worker_id = $5 RETURNING *",
+        )
+        .bind(addresses)
+        .bind(sequence)
+        .bind(hash.clone())
+        .bind(created_at)
+        .bind(worker_id)
+        .fetch_all(&mut tx)
+        .await?;
+        sqlx::query(
+            "UPDATE wallet.account SET head = $2, hash = $3 WHERE address = ANY($1) AND (head > $2 OR (head = $2 AND hash <> $3)) AND worker_id = $4",
+        )
+        .bind(addresses)
+        .bind(sequence)
+        .bind(hash)
+        .bind(worker_id)
+        .execute(&mut tx)
+        .await?;
+        // other workers may still be behind the fork, their blocks stay
+        sqlx::query("DELETE FROM wallet.block WHERE worker_id = $1 AND sequence > $2")
+            .bind(worker_id)
+            .bind(sequence)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(events)
+    }
+
+    /// Reorg events of an account with id greater than `since`
+    pub async fn get_reorg_events(
+        &self,
+        address: String,
+        since: i64,
+    ) -> Result<Vec<ReorgEvent>, sqlx::Error> {
+        sqlx::query_as::<_, ReorgEvent>(
+            "SELECT * FROM wallet.reorg WHERE address = $1 AND id > $2 ORDER BY id",
+        )
+        .bind(address)
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await
+    }
+
     pub async fn delete(&self, address: String) -> Result<String, sqlx::Error> {
         let result = sqlx::query("DELETE FROM wallet.account WHERE address = $1 RETURNING address")
             .bind(address)
@@ -65,6 +279,120 @@ impl PgHandler {
     }
 }
 
+async fn insert_notes(
+    tx: &mut Transaction<'_, Postgres>,
+    notes: &[DecryptedNote],
+) -> Result<(), sqlx::Error> {
+    for note in notes.iter() {
+        sqlx::query(
+            "INSERT INTO wallet.note (address, commitment, tx_hash, position, sequence, block_hash, asset_id, value, memo, sender, owner, nullifier, spent_tx, spent_sequence) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14) ON CONFLICT (address, commitment) DO NOTHING"
+        )
+        .bind(note.address.clone())
+        .bind(note.commitment.clone())
+        .bind(note.tx_hash.clone())
+        .bind(note.position)
+        .bind(note.sequence)
+        .bind(note.block_hash.clone())
+        .bind(note.asset_id.clone())
+        .bind(note.value)
+        .bind(note.memo.clone())
+        .bind(note.sender.clone())
+        .bind(note.owner.clone())
+        .bind(note.nullifier.clone())
+        .bind(note.spent_tx.clone())
+        .bind(note.spent_sequence)
+        .execute(&mut *tx)
+        .await?;
+        insert_transaction(
+            tx,
+            &note.address,
+            &note.tx_hash,
+            note.sequence,
+            &note.block_hash,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+async fn spend_notes(
+    tx: &mut Transaction<'_, Postgres>,
+    spends: &[SpentNullifier],
+) -> Result<Vec<String>, sqlx::Error> {
+    let mut spenders = vec![];
+    for spend in spends.iter() {
+        let owners: Vec<String> = sqlx::query(
+            "UPDATE wallet.note SET spent_tx = $1, spent_sequence = $2 WHERE nullifier = $3 RETURNING address",
+        )
+        .bind(spend.tx_hash.clone())
+        .bind(spend.sequence)
+        .bind(spend.nullifier.clone())
+        .fetch_all(&mut *tx)
+        .await?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+        for owner in owners {
+            sqlx::query(
+                "INSERT INTO wallet.nullifier (nullifier, address, tx_hash, sequence) VALUES ($1, $2, $3, $4) ON CONFLICT (nullifier) DO NOTHING",
+            )
+            .bind(spend.nullifier.clone())
+            .bind(owner.clone())
+            .bind(spend.tx_hash.clone())
+            .bind(spend.sequence)
+            .execute(&mut *tx)
+            .await?;
+            insert_transaction(
+                tx,
+                &owner,
+                &spend.tx_hash,
+                spend.sequence,
+                &spend.block_hash,
+            )
+            .await?;
+            spenders.push(owner);
+        }
+    }
+    Ok(spenders)
+}
+
+async fn insert_transaction(
+    tx: &mut Transaction<'_, Postgres>,
+    address: &str,
+    hash: &str,
+    sequence: i64,
+    block_hash: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO wallet.transaction (address, hash, sequence, block_hash) VALUES ($1, $2, $3, $4) ON CONFLICT (address, hash) DO NOTHING",
+    )
+    .bind(address)
+    .bind(hash)
+    .bind(sequence)
+    .bind(block_hash)
+    .execute(&mut *tx)
+    .await?;
+    Ok(())
+}
+
+/// Recompute balances of `addresses` from their unspent notes
+async fn refresh_balances(
+    tx: &mut Transaction<'_, Postgres>,
+    addresses: &[String],
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM wallet.balance WHERE address = ANY($1)")
+        .bind(addresses)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        "INSERT INTO wallet.balance (address, asset_id, value) SELECT address, asset_id, SUM(value) FROM wallet.note WHERE address = ANY($1) AND nullifier IS NOT NULL AND spent_tx IS NULL GROUP BY address, asset_id",
+    )
+    .bind(addresses)
+    .execute(&mut *tx)
+    .await?;
+    Ok(())
+}
+
 #[async_trait::async_trait]
 impl DBHandler for PgHandler {
     fn from_config(config: &crate::config::DbConfig) -> Self {
@@ -79,7 +407,12 @@ impl DBHandler for PgHandler {
         Self::new(pool)
     }
 
-    async fn save_account(&self, account: Account, _worker_id: u32) -> Result<String, OreoError> {
+    async fn save_account(
+        &self,
+        mut account: Account,
+        worker_id: u32,
+    ) -> Result<String, OreoError> {
+        account.worker_id = worker_id as i32;
         let old_account = self.get_one(account.address.clone()).await;
         match old_account {
             Ok(_) => Err(OreoError::Duplicate(account.address)),
@@ -105,6 +438,94 @@ impl DBHandler for PgHandler {
             _ => OreoError::DBError,
         })
     }
+
+    async fn save_notes(&self, notes: Vec<DecryptedNote>) -> Result<(), OreoError> {
+        let result: Result<(), sqlx::Error> = async {
+            let mut tx = self.pool.begin().await?;
+            insert_notes(&mut tx, &notes).await?;
+            let addresses: Vec<String> = notes.iter().map(|note| note.address.clone()).collect();
+            refresh_balances(&mut tx, &addresses).await?;
+            tx.commit().await
+        }
+        .await;
+        result.map_err(|_| OreoError::DBError)
+    }
+
+    async fn mark_spent(&self, spends: Vec<SpentNullifier>) -> Result<(), OreoError> {
+        let result: Result<(), sqlx::Error> = async {
+            let mut tx = self.pool.begin().await?;
+            let spenders = spend_notes(&mut tx, &spends).await?;
+            refresh_balances(&mut tx, &spenders).await?;
+            tx.commit().await
+        }
+        .await;
+        result.map_err(|_| OreoError::DBError)
+    }
+
+    async fn get_unspent_notes(
+        &self,
+        address: String,
+        asset_id: Option<String>,
+    ) -> Result<Vec<DecryptedNote>, OreoError> {
+        sqlx::query_as::<_, DecryptedNote>(
+            "SELECT * FROM wallet.note WHERE address = $1 AND ($2::TEXT IS NULL OR asset_id = $2) AND nullifier IS NOT NULL AND spent_tx IS NULL ORDER BY sequence, position",
+        )
+        .bind(address)
+        .bind(asset_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| OreoError::DBError)
+    }
+
+    async fn get_transactions(
+        &self,
+        address: String,
+        limit: i64,
+    ) -> Result<Vec<AccountTransaction>, OreoError> {
+        sqlx::query_as::<_, AccountTransaction>(
+            "SELECT * FROM wallet.transaction WHERE address = $1 ORDER BY sequence DESC, hash LIMIT $2",
+        )
+        .bind(address)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| OreoError::DBError)
+    }
+
+    async fn get_owned_notes(
+        &self,
+        address: String,
+        spent_after: i64,
+    ) -> Result<Vec<DecryptedNote>, OreoError> {
+        sqlx::query_as::<_, DecryptedNote>(
+            "SELECT * FROM wallet.note WHERE address = $1 AND nullifier IS NOT NULL AND (spent_sequence IS NULL OR spent_sequence > $2) ORDER BY sequence, position",
+        )
+        .bind(address)
+        .bind(spent_after)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| OreoError::DBError)
+    }
+
+    async fn get_balances(&self, address: String) -> Result<Vec<AssetBalance>, OreoError> {
+        sqlx::query_as::<_, AssetBalance>(
+            "SELECT address, asset_id, value::TEXT AS value FROM wallet.balance WHERE address = $1 ORDER BY asset_id",
+        )
+        .bind(address)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| OreoError::DBError)
+    }
+
+    async fn get_worker_statuses(&self) -> Result<Vec<WorkerStatus>, OreoError> {
+        self.get_workers().await.map_err(|_| OreoError::DBError)
+    }
+
+    async fn get_reorgs(&self, address: String, since: i64) -> Result<Vec<ReorgEvent>, OreoError> {
+        self.get_reorg_events(address, since)
+            .await
+            .map_err(|_| OreoError::DBError)
+    }
 }
 
 #[cfg(test)]
@@ -112,8 +533,10 @@ mod tests {
     use sqlx_db_tester::TestDb;
 
     use crate::{
-        constants::{MAINNET_GENESIS_HASH, MAINNET_GENESIS_SEQUENCE},
-        db_handler::{address_to_name, Account, DBHandler},
+        constants::{Network, MAINNET_GENESIS_HASH, MAINNET_GENESIS_SEQUENCE},
+        db_handler::{
+            address_to_name, Account, AssetBalance, DBHandler, DecryptedNote, SpentNullifier,
+        },
     };
 
     use super::PgHandler;
@@ -138,6 +561,8 @@ mod tests {
             out_vk: OUT_VK.to_string(),
             vk: VK.to_string(),
             address: ADDRESS.to_string(),
+            network_id: Network::Mainnet.id(),
+            worker_id: 0,
         }
     }
 
@@ -180,4 +605,187 @@ mod tests {
         let should_error = pg_handler.remove_account(saved).await;
         assert!(should_error.is_err());
     }
+
+    fn get_test_note(commitment: &str, nullifier: &str) -> DecryptedNote {
+        DecryptedNote {
+            address: ADDRESS.to_string(),
+            commitment: commitment.to_string(),
+            tx_hash: "aa".repeat(32),
+            position: 10,
+            sequence: 2,
+            block_hash: "bb".repeat(32),
+            asset_id: "cc".repeat(32),
+            value: 100,
+            memo: "memo".to_string(),
+            sender: ADDRESS.to_string(),
+            owner: ADDRESS.to_string(),
+            nullifier: Some(nullifier.to_string()),
+            spent_tx: None,
+            spent_sequence: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn save_scanned_block_should_work_pg() {
+        let tdb = get_tdb();
+        let pool = tdb.get_pool().await;
+        let pg_handler = PgHandler::new(pool);
+        let account = get_test_account();
+        pg_handler.save_account(account.clone(), 0).await.unwrap();
+        let note = get_test_note(&"01".repeat(32), &"02".repeat(32));
+        pg_handler
+            .save_scanned_block(
+                0,
+                2,
+                "bb".repeat(32),
+                &[ADDRESS.to_string()],
+                std::slice::from_ref(&note),
+                &[],
+            )
+            .await
+            .unwrap();
+        let saved = pg_handler.get_account(ADDRESS.to_string()).await.unwrap();
+        assert_eq!(saved.head, 2);
+        assert_eq!(saved.hash, "bb".repeat(32));
+        assert_eq!(
+            pg_handler.get_notes(ADDRESS.to_string()).await.unwrap(),
+            vec![note]
+        );
+
+        let spend = SpentNullifier {
+            nullifier: "02".repeat(32),
+            tx_hash: "dd".repeat(32),
+            sequence: 3,
+            block_hash: "ee".repeat(32),
+        };
+        pg_handler
+            .save_scanned_block(0, 3, "ee".repeat(32), &[ADDRESS.to_string()], &[], &[spend])
+            .await
+            .unwrap();
+        let notes = pg_handler.get_notes(ADDRESS.to_string()).await.unwrap();
+        assert_eq!(notes[0].spent_tx, Some("dd".repeat(32)));
+        assert_eq!(notes[0].spent_sequence, Some(3));
+        assert_eq!(
+            pg_handler.get_block_hash(0, 3).await.unwrap(),
+            Some("ee".repeat(32))
+        );
+    }
+
+    #[tokio::test]
+    async fn rollback_should_work_pg() {
+        let tdb = get_tdb();
+        let pool = tdb.get_pool().await;
+        let pg_handler = PgHandler::new(pool);
+        let account = get_test_account();
+        pg_handler.save_account(account.clone(), 0).await.unwrap();
+        let kept = get_test_note(&"01".repeat(32), &"02".repeat(32));
+        let mut dropped = get_test_note(&"03".repeat(32), &"04".repeat(32));
+        dropped.sequence = 3;
+        let spend = SpentNullifier {
+            nullifier: "02".repeat(32),
+            tx_hash: "dd".repeat(32),
+            sequence: 3,
+            block_hash: "ee".repeat(32),
+        };
+        let addresses = [ADDRESS.to_string()];
+        pg_handler
+            .save_scanned_block(
+                0,
+                2,
+                "bb".repeat(32),
+                &addresses,
+                std::slice::from_ref(&kept),
+                &[],
+            )
+            .await
+            .unwrap();
+        pg_handler
+            .save_scanned_block(0, 3, "ee".repeat(32), &addresses, &[dropped], &[spend])
+            .await
+            .unwrap();
+        pg_handler
+            .save_scanned_block(1, 3, "ee".repeat(32), &[], &[], &[])
+            .await
+            .unwrap();
+
+        let events = pg_handler
+            .rollback(0, &addresses, 2, "bb".repeat(32), 100)
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].from_sequence, 3);
+        assert_eq!(events[0].to_hash, "bb".repeat(32));
+        let saved = pg_handler.get_account(ADDRESS.to_string()).await.unwrap();
+        assert_eq!(saved.head, 2);
+        assert_eq!(saved.hash, "bb".repeat(32));
+        assert_eq!(
+            pg_handler.get_notes(ADDRESS.to_string()).await.unwrap(),
+            vec![kept]
+        );
+        assert_eq!(pg_handler.get_block_hash(0, 3).await.unwrap(), None);
+        assert_eq!(
+            pg_handler.get_block_hash(1, 3).await.unwrap(),
+            Some("ee".repeat(32))
+        );
+        assert_eq!(
+            pg_handler
+                .get_reorg_events(ADDRESS.to_string(), 0)
+                .await
+                .unwrap(),
+            events
+        );
+    }
+
+    #[tokio::test]
+    async fn unspent_notes_should_work_pg() {
+        let tdb = get_tdb();
+        let pool = tdb.get_pool().await;
+        let pg_handler = PgHandler::new(pool);
+        let first = get_test_note(&"01".repeat(32), &"02".repeat(32));
+        let mut second = get_test_note(&"03".repeat(32), &"04".repeat(32));
+        second.tx_hash = "ab".repeat(32);
+        second.value = 50;
+        pg_handler
+            .save_notes(vec![first.clone(), second.clone()])
+            .await
+            .unwrap();
+        assert_eq!(
+            pg_handler.get_balances(ADDRESS.to_string()).await.unwrap(),
+            vec![AssetBalance {
+                address: ADDRESS.to_string(),
+                asset_id: "cc".repeat(32),
+                value: "150".to_string(),
+            }]
+        );
+
+        pg_handler
+            .mark_spent(vec![SpentNullifier {
+                nullifier: "02".repeat(32),
+                tx_hash: "dd".repeat(32),
+                sequence: 3,
+                block_hash: "ee".repeat(32),
+            }])
+            .await
+            .unwrap();
+        let unspent = pg_handler
+            .get_unspent_notes(ADDRESS.to_string(), Some("cc".repeat(32)))
+            .await
+            .unwrap();
+        assert_eq!(unspent, vec![second]);
+        assert!(pg_handler
+            .get_unspent_notes(ADDRESS.to_string(), Some("ff".repeat(32)))
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            pg_handler.get_balances(ADDRESS.to_string()).await.unwrap()[0].value,
+            "50"
+        );
+        let transactions = pg_handler
+            .get_transactions(ADDRESS.to_string(), 10)
+            .await
+            .unwrap();
+        assert_eq!(transactions.len(), 3);
+        assert_eq!(transactions[0].hash, "dd".repeat(32));
+    }
 }
@@ -1,15 +1,21 @@
 use redis::{
     aio::MultiplexedConnection, AsyncCommands, Client, ErrorKind, FromRedisValue, RedisResult,
 };
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use substring::Substring;
 use tracing::info;
 
-use super::{Account, DBHandler};
+use super::{
+    Account, AccountTransaction, AssetBalance, DBHandler, DecryptedNote, ReorgEvent,
+    SpentNullifier, WorkerStatus,
+};
 use crate::{config::DbConfig, error::OreoError};
 
 pub const REDIS_ACCOUNT_KEY: &str = "IRONACCOUNT";
 pub const REDIS_ACCOUNT_KEY_V1: &str = "IRONACCOUNTV1";
+pub const REDIS_NOTE_KEY: &str = "IRONNOTE";
+pub const REDIS_NULLIFIER_KEY: &str = "IRONNULLIFIER";
+pub const REDIS_TRANSACTION_KEY: &str = "IRONTX";
 
 #[derive(Debug, Clone)]
 pub struct RedisClient {
@@ -55,11 +61,26 @@ impl RedisClient {
         FromRedisValue::from_redis_value(&val)
     }
 
+    pub async fn hget_opt(&self, key: &str, field: &str) -> RedisResult<Option<String>> {
+        let mut con = self.get_con().await?;
+        con.hget(key, field).await
+    }
+
+    pub async fn hsetnx(&self, key: &str, field: &str, value: &str) -> RedisResult<bool> {
+        let mut con = self.get_con().await?;
+        con.hset_nx(key, field, value).await
+    }
+
     pub async fn hdel(&self, key: &str, field: &str) -> RedisResult<()> {
         let mut con = self.get_con().await?;
         con.hdel(key, field).await
     }
 
+    pub async fn del(&self, key: &str) -> RedisResult<()> {
+        let mut con = self.get_con().await?;
+        con.del(key).await
+    }
+
     pub async fn get_str(&self, key: &str) -> RedisResult<String> {
         let mut con = self.get_con().await?;
         let value = con.get(key).await?;
@@ -69,7 +90,12 @@ impl RedisClient {
 
 #[async_trait::async_trait]
 impl DBHandler for RedisClient {
-    async fn save_account(&self, account: Account, _worker_id: u32) -> Result<String, OreoError> {
+    async fn save_account(
+        &self,
+        mut account: Account,
+        worker_id: u32,
+    ) -> Result<String, OreoError> {
+        account.worker_id = worker_id as i32;
         let address = account.address.clone();
         match self.hget(&self.db_name, &address).await {
             Ok(_) => {
@@ -129,12 +155,177 @@ impl DBHandler for RedisClient {
         }
     }
 
+    async fn save_notes(&self, notes: Vec<DecryptedNote>) -> Result<(), OreoError> {
+        for note in notes.into_iter() {
+            let key = format!("{}:{}", REDIS_NOTE_KEY, note.address);
+            let value = serde_json::to_string(&note)
+                .map_err(|_| OreoError::SeralizeError(note.commitment.clone()))?;
+            let saved = self
+                .hsetnx(&key, &note.commitment, &value)
+                .await
+                .map_err(|_| OreoError::DBError)?;
+            if !saved {
+                continue;
+            }
+            if let Some(nullifier) = &note.nullifier {
+                // nullifier -> address:commitment of the owned note
+                let owned = format!("{}:{}", note.address, note.commitment);
+                self.hset(REDIS_NULLIFIER_KEY, nullifier, &owned)
+                    .await
+                    .map_err(|_| OreoError::DBError)?;
+            }
+            self.save_transaction(AccountTransaction {
+                address: note.address,
+                hash: note.tx_hash,
+                sequence: note.sequence,
+                block_hash: note.block_hash,
+            })
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn mark_spent(&self, spends: Vec<SpentNullifier>) -> Result<(), OreoError> {
+        for spend in spends.into_iter() {
+            let owned = self
+                .hget_opt(REDIS_NULLIFIER_KEY, &spend.nullifier)
+                .await
+                .map_err(|_| OreoError::DBError)?;
+            let Some((address, commitment)) = owned.as_deref().and_then(|x| x.split_once(':'))
+            else {
+                continue;
+            };
+            let key = format!("{}:{}", REDIS_NOTE_KEY, address);
+            let note = self
+                .hget(&key, commitment)
+                .await
+                .map_err(|_| OreoError::DBError)?;
+            let mut note = serde_json::from_str::<DecryptedNote>(&note)
+                .map_err(|_| OreoError::ParseError(commitment.to_string()))?;
+            note.spent_tx = Some(spend.tx_hash.clone());
+            note.spent_sequence = Some(spend.sequence);
+            let value = serde_json::to_string(&note)
+                .map_err(|_| OreoError::SeralizeError(commitment.to_string()))?;
+            self.hset(&key, commitment, &value)
+                .await
+                .map_err(|_| OreoError::DBError)?;
+            self.save_transaction(AccountTransaction {
+                address: address.to_string(),
+                hash: spend.tx_hash,
+                sequence: spend.sequence,
+                block_hash: spend.block_hash,
+            })
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn get_unspent_notes(
+        &self,
+        address: String,
+        asset_id: Option<String>,
+    ) -> Result<Vec<DecryptedNote>, OreoError> {
+        Ok(self
+            .get_notes(&address)
+            .await?
+            .into_iter()
+            .filter(|note| note.is_unspent())
+            .filter(|note| asset_id.as_ref().map_or(true, |x| &note.asset_id == x))
+            .collect())
+    }
+
+    async fn get_transactions(
+        &self,
+        address: String,
+        limit: i64,
+    ) -> Result<Vec<AccountTransaction>, OreoError> {
+        let key = format!("{}:{}", REDIS_TRANSACTION_KEY, address);
+        let transactions = self.hgetall(&key).await.map_err(|_| OreoError::DBError)?;
+        let mut transactions = transactions
+            .into_values()
+            .map(|tx| {
+                serde_json::from_str::<AccountTransaction>(&tx)
+                    .map_err(|_| OreoError::ParseError(address.clone()))
+            })
+            .collect::<Result<Vec<AccountTransaction>, OreoError>>()?;
+        transactions.sort_by(|a, b| b.sequence.cmp(&a.sequence).then(a.hash.cmp(&b.hash)));
+        transactions.truncate(limit.max(0) as usize);
+        Ok(transactions)
+    }
+
+    async fn get_owned_notes(
+        &self,
+        address: String,
+        spent_after: i64,
+    ) -> Result<Vec<DecryptedNote>, OreoError> {
+        Ok(self
+            .get_notes(&address)
+            .await?
+            .into_iter()
+            .filter(|note| note.is_owned())
+            .filter(|note| note.spent_sequence.map_or(true, |x| x > spent_after))
+            .collect())
+    }
+
+    async fn get_balances(&self, address: String) -> Result<Vec<AssetBalance>, OreoError> {
+        let mut balances = BTreeMap::new();
+        for note in self.get_unspent_notes(address.clone(), None).await? {
+            *balances.entry(note.asset_id).or_insert(0i128) += note.value as i128;
+        }
+        Ok(balances
+            .into_iter()
+            .map(|(asset_id, value)| AssetBalance {
+                address: address.clone(),
+                asset_id,
+                value: value.to_string(),
+            })
+            .collect())
+    }
+
+    async fn get_worker_statuses(&self) -> Result<Vec<WorkerStatus>, OreoError> {
+        Ok(vec![])
+    }
+
+    async fn get_reorgs(
+        &self,
+        _address: String,
+        _since: i64,
+    ) -> Result<Vec<ReorgEvent>, OreoError> {
+        Ok(vec![])
+    }
+
     fn from_config(config: &DbConfig) -> Self {
         info!("Redis handler selected");
         RedisClient::connect(&config.server_url(), config.default_pool_size).unwrap()
     }
 }
 
+impl RedisClient {
+    async fn save_transaction(&self, transaction: AccountTransaction) -> Result<(), OreoError> {
+        let key = format!("{}:{}", REDIS_TRANSACTION_KEY, transaction.address);
+        let value = serde_json::to_string(&transaction)
+            .map_err(|_| OreoError::SeralizeError(transaction.hash.clone()))?;
+        self.hsetnx(&key, &transaction.hash, &value)
+            .await
+            .map_err(|_| OreoError::DBError)?;
+        Ok(())
+    }
+
+    async fn get_notes(&self, address: &str) -> Result<Vec<DecryptedNote>, OreoError> {
+        let key = format!("{}:{}", REDIS_NOTE_KEY, address);
+        let notes = self.hgetall(&key).await.map_err(|_| OreoError::DBError)?;
+        let mut notes = notes
+            .into_values()
+            .map(|note| {
+                serde_json::from_str::<DecryptedNote>(&note)
+                    .map_err(|_| OreoError::ParseError(address.to_string()))
+            })
+            .collect::<Result<Vec<DecryptedNote>, OreoError>>()?;
+        notes.sort_by_key(|note| (note.sequence, note.position));
+        Ok(notes)
+    }
+}
+
 pub fn address_to_name(address: &str) -> String {
     address.substring(0, 10).into()
 }
@@ -150,13 +341,18 @@ mod tests {
     //   Outgoing View Key  cee4ff41d7d8da5eedc6493134981eaad7b26a8b0291a4eac9ba95090fa47bf7
     //       Address  d63ba13d7c35caf942c64d5139b948b885ec931977a3f248c13e7f3c1bd0aa64
 
+    use rand::random;
+
     use super::address_to_name;
     use super::RedisClient;
+    use super::{REDIS_NOTE_KEY, REDIS_NULLIFIER_KEY, REDIS_TRANSACTION_KEY};
     use crate::config::DbConfig;
+    use crate::constants::Network;
     use crate::constants::MAINNET_GENESIS_HASH;
     use crate::constants::MAINNET_GENESIS_SEQUENCE;
     use crate::db_handler::Account;
     use crate::db_handler::DBHandler;
+    use crate::db_handler::{DecryptedNote, SpentNullifier};
     use crate::error::OreoError;
 
     const VK: &str = "4ae4eb9606ba57b3b17a444100a9ac6453cd67e6fe4c860e63a2e18b1200978ab5ecce68e8639d5016cbe73b0ea9a3c8e906fc881af2e9ccfa7a7b63fb73d555";
@@ -175,6 +371,8 @@ mod tests {
             out_vk: OUT_VK.to_string(),
             vk: VK.to_string(),
             address: ADDRESS.to_string(),
+            network_id: Network::Mainnet.id(),
+            worker_id: 0,
         }
     }
 
@@ -217,4 +415,74 @@ mod tests {
         let expected = OreoError::NoImported(ADDRESS.to_string());
         assert_eq!(expected, should_error_account);
     }
+
+    async fn clean_notes(db_handler: &RedisClient, address: &str, nullifier: &str) {
+        for key in [REDIS_NOTE_KEY, REDIS_TRANSACTION_KEY] {
+            db_handler
+                .del(&format!("{}:{}", key, address))
+                .await
+                .unwrap();
+        }
+        db_handler
+            .hdel(REDIS_NULLIFIER_KEY, nullifier)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn unspent_notes_should_work_redis() {
+        let db_handler = get_tdb();
+        // fresh address and nullifier every run so account tests and earlier runs don't interfere
+        let address = hex::encode(random::<[u8; 32]>());
+        let nullifier = hex::encode(random::<[u8; 32]>());
+        clean_notes(&db_handler, &address, &nullifier).await;
+        let note = DecryptedNote {
+            address: address.clone(),
+            commitment: "01".repeat(32),
+            tx_hash: "aa".repeat(32),
+            position: 10,
+            sequence: 2,
+            block_hash: "bb".repeat(32),
+            asset_id: "cc".repeat(32),
+            value: 100,
+            memo: "memo".to_string(),
+            sender: address.clone(),
+            owner: address.clone(),
+            nullifier: Some(nullifier.clone()),
+            spent_tx: None,
+            spent_sequence: None,
+        };
+        db_handler.save_notes(vec![note.clone()]).await.unwrap();
+        let unspent = db_handler
+            .get_unspent_notes(address.clone(), Some("cc".repeat(32)))
+            .await
+            .unwrap();
+        assert_eq!(unspent, vec![note]);
+        assert_eq!(
+            db_handler.get_balances(address.clone()).await.unwrap()[0].value,
+            "100"
+        );
+
+        db_handler
+            .mark_spent(vec![SpentNullifier {
+                nullifier: nullifier.clone(),
+                tx_hash: "dd".repeat(32),
+                sequence: 3,
+                block_hash: "ee".repeat(32),
+            }])
+            .await
+            .unwrap();
+        assert!(db_handler
+            .get_unspent_notes(address.clone(), None)
+            .await
+            .unwrap()
+            .is_empty());
+        let transactions = db_handler
+            .get_transactions(address.clone(), 1)
+            .await
+            .unwrap();
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].hash, "dd".repeat(32));
+        clean_notes(&db_handler, &address, &nullifier).await;
+    }
 }
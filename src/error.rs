@@ -1,12 +1,25 @@
 use axum::{
-    http::StatusCode,
+    extract::Request,
+    http::{HeaderValue, StatusCode},
+    middleware::Next,
     response::{IntoResponse, Response},
     Json,
 };
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use serde_json::{json, Value};
 use thiserror::Error;
 
+use crate::rpc_handler::RpcError;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Errors returned by every route, see the errors section of the README for the catalogue
 #[derive(Debug, Error, Serialize, Deserialize, PartialEq)]
 pub enum OreoError {
     #[error("The account `{0}` is already imported")]
@@ -19,14 +32,14 @@ pub enum OreoError {
     Syncing,
     #[error("Internal db error")]
     DBError,
-    #[error("Internal Ironfish rpc error")]
-    InternalRpcError,
-    #[error("The `{0}` spend circuit can not generate proof")]
-    GenerateSpendProofFailed(u32),
-    #[error("The `{0}` output circuit can not generate proof")]
-    GenerateOutputProofFailed(u32),
-    #[error("The `{0}` mint asset circuit can not generate proof")]
-    GenerateMintAssetProofFailed(u32),
+    #[error("Ironfish rpc error `{}`: {}", .0.code, .0.message)]
+    InternalRpcError(RpcError),
+    #[error("Circuits can not generate proof: {}", describe_failures(.0))]
+    GenerateSpendProofFailed(Vec<CircuitFailure>),
+    #[error("Circuits can not generate proof: {}", describe_failures(.0))]
+    GenerateOutputProofFailed(Vec<CircuitFailure>),
+    #[error("Circuits can not generate proof: {}", describe_failures(.0))]
+    GenerateMintAssetProofFailed(Vec<CircuitFailure>),
     #[error("Balance not enough")]
     BalanceNotEnough,
     #[error("Bad mint request")]
@@ -37,34 +50,351 @@ pub enum OreoError {
     SeralizeError(String),
     #[error("Failed to parse data `{0}`")]
     ParseError(String),
+    #[error("The block `{0}` does not belong to the configured network")]
+    NetworkMismatch(String),
+    #[error("Prover queue is full, retry later")]
+    ProverBusy,
+    #[error("Proof job `{0}` not found")]
+    JobNotFound(String),
+    #[error("Invalid proof request, {0}")]
+    InvalidProofRequest(String),
+    #[error("Generated proofs failed self check: {}", describe_failures(.0))]
+    ProofSelfCheckFailed(Vec<CircuitFailure>),
+    #[error("Missing or invalid prover credentials")]
+    Unauthorized,
+    #[error("Quota of client `{0}` exceeded, retry later")]
+    QuotaExceeded(String),
+    #[error("Internal prover error")]
+    InternalProverError,
+    #[error("Prover params are still loading")]
+    ProverNotReady,
+    #[error("No prover worker could prove the request, {0}")]
+    WorkersUnavailable(String),
+    #[error("Request timed out")]
+    RequestTimeout,
+    #[error("The account already exists on the node, {0}")]
+    NodeAccountExists(String),
+    #[error("Not found on the node, {0}")]
+    NodeNotFound(String),
+    #[error("Request refused by the node, {0}")]
+    NodeValidation(String),
+    #[error("Transaction expired, {0}")]
+    TransactionExpired(String),
+    #[error("Invalid transaction fee, {0}")]
+    InvalidFee(String),
+    #[error("Not authenticated to the node")]
+    NodeUnauthenticated,
+    #[error("Route not served by the node, {0}")]
+    NodeRouteNotFound(String),
+    #[error("Invalid proof generation key, {0}")]
+    InvalidProofGenerationKey(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CircuitKind {
+    Spend,
+    Output,
+    Mint,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FailureReason {
+    /// Circuit bytes could not be read
+    Deserialize,
+    /// Circuit was read but proving it failed
+    Synthesis,
+    /// Proof was generated but does not verify against its public inputs
+    Verification,
+}
+
+/// One circuit of a proof request which could not be proven
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CircuitFailure {
+    pub kind: CircuitKind,
+    pub index: u32,
+    pub reason: FailureReason,
+    pub message: String,
+}
+
+impl fmt::Display for CircuitKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitKind::Spend => write!(f, "spend"),
+            CircuitKind::Output => write!(f, "output"),
+            CircuitKind::Mint => write!(f, "mint"),
+        }
+    }
+}
+
+impl fmt::Display for FailureReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailureReason::Deserialize => write!(f, "deserialize"),
+            FailureReason::Synthesis => write!(f, "synthesis"),
+            FailureReason::Verification => write!(f, "verification"),
+        }
+    }
+}
+
+fn describe_failures(failures: &[CircuitFailure]) -> String {
+    failures
+        .iter()
+        .map(|x| format!("{} `{}` ({})", x.kind, x.index, x.reason))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+impl OreoError {
+    /// Proof failure error, the code follows the kind of the first failing circuit,
+    /// proofs failing verification have their own code
+    pub fn proof_failed(mut failures: Vec<CircuitFailure>) -> Self {
+        failures.sort_by_key(|x| (x.kind, x.index));
+        if failures
+            .iter()
+            .any(|x| x.reason == FailureReason::Verification)
+        {
+            return OreoError::ProofSelfCheckFailed(failures);
+        }
+        match failures.first().map(|x| x.kind) {
+            Some(CircuitKind::Output) => OreoError::GenerateOutputProofFailed(failures),
+            Some(CircuitKind::Mint) => OreoError::GenerateMintAssetProofFailed(failures),
+            _ => OreoError::GenerateSpendProofFailed(failures),
+        }
+    }
+
+    pub fn failures(&self) -> Option<&[CircuitFailure]> {
+        match self {
+            OreoError::GenerateSpendProofFailed(failures)
+            | OreoError::GenerateOutputProofFailed(failures)
+            | OreoError::GenerateMintAssetProofFailed(failures)
+            | OreoError::ProofSelfCheckFailed(failures) => Some(failures),
+            _ => None,
+        }
+    }
+
+    pub fn code(&self) -> u16 {
+        match self {
+            OreoError::DBError => 600,
+            OreoError::Duplicate(_) => 601,
+            OreoError::NoImported(_) => 602,
+            OreoError::Scanning(_) => 603,
+            OreoError::Syncing => 604,
+            OreoError::InternalRpcError(_) => 605,
+            OreoError::GenerateSpendProofFailed(_) => 606,
+            OreoError::GenerateOutputProofFailed(_) => 607,
+            OreoError::GenerateMintAssetProofFailed(_) => 608,
+            OreoError::BalanceNotEnough => 609,
+            OreoError::BadMintRequest => 610,
+            OreoError::TransactionNotFound => 611,
+            OreoError::SeralizeError(_) => 612,
+            OreoError::ParseError(_) => 613,
+            OreoError::NetworkMismatch(_) => 614,
+            OreoError::ProverBusy => 615,
+            OreoError::JobNotFound(_) => 616,
+            OreoError::InvalidProofRequest(_) => 617,
+            OreoError::ProofSelfCheckFailed(_) => 618,
+            OreoError::Unauthorized => 619,
+            OreoError::QuotaExceeded(_) => 620,
+            OreoError::InternalProverError => 621,
+            OreoError::ProverNotReady => 622,
+            OreoError::WorkersUnavailable(_) => 623,
+            OreoError::RequestTimeout => 624,
+            OreoError::NodeAccountExists(_) => 625,
+            OreoError::NodeNotFound(_) => 626,
+            OreoError::NodeValidation(_) => 627,
+            OreoError::TransactionExpired(_) => 628,
+            OreoError::InvalidFee(_) => 629,
+            OreoError::NodeUnauthenticated => 630,
+            OreoError::NodeRouteNotFound(_) => 631,
+            OreoError::InvalidProofGenerationKey(_) => 632,
+        }
+    }
+
+    /// Stable name of the error, unlike the message it never changes
+    pub fn kind(&self) -> &'static str {
+        match self {
+            OreoError::DBError => "database-error",
+            OreoError::Duplicate(_) => "account-exists",
+            OreoError::NoImported(_) => "account-not-imported",
+            OreoError::Scanning(_) => "account-not-scanned",
+            OreoError::Syncing => "node-syncing",
+            OreoError::InternalRpcError(_) => "rpc-error",
+            OreoError::GenerateSpendProofFailed(_) => "spend-proof-failed",
+            OreoError::GenerateOutputProofFailed(_) => "output-proof-failed",
+            OreoError::GenerateMintAssetProofFailed(_) => "mint-proof-failed",
+            OreoError::BalanceNotEnough => "insufficient-balance",
+            OreoError::BadMintRequest => "bad-mint-request",
+            OreoError::TransactionNotFound => "transaction-not-found",
+            OreoError::SeralizeError(_) => "serialize-error",
+            OreoError::ParseError(_) => "parse-error",
+            OreoError::NetworkMismatch(_) => "network-mismatch",
+            OreoError::ProverBusy => "prover-busy",
+            OreoError::JobNotFound(_) => "job-not-found",
+            OreoError::InvalidProofRequest(_) => "invalid-proof-request",
+            OreoError::ProofSelfCheckFailed(_) => "proof-self-check-failed",
+            OreoError::Unauthorized => "unauthorized",
+            OreoError::QuotaExceeded(_) => "quota-exceeded",
+            OreoError::InternalProverError => "internal-prover-error",
+            OreoError::ProverNotReady => "prover-not-ready",
+            OreoError::WorkersUnavailable(_) => "workers-unavailable",
+            OreoError::RequestTimeout => "request-timeout",
+            OreoError::NodeAccountExists(_) => "node-account-exists",
+            OreoError::NodeNotFound(_) => "node-not-found",
+            OreoError::NodeValidation(_) => "node-validation",
+            OreoError::TransactionExpired(_) => "transaction-expired",
+            OreoError::InvalidFee(_) => "invalid-fee",
+            OreoError::NodeUnauthenticated => "node-unauthenticated",
+            OreoError::NodeRouteNotFound(_) => "node-route-not-found",
+            OreoError::InvalidProofGenerationKey(_) => "invalid-proof-generation-key",
+        }
+    }
+
+    /// HTTP status of the error response
+    pub fn status(&self) -> StatusCode {
+        match self {
+            OreoError::BadMintRequest
+            | OreoError::NetworkMismatch(_)
+            | OreoError::InvalidProofRequest(_)
+            | OreoError::NodeValidation(_)
+            | OreoError::TransactionExpired(_)
+            | OreoError::InvalidFee(_)
+            | OreoError::InvalidProofGenerationKey(_) => StatusCode::BAD_REQUEST,
+            OreoError::Unauthorized => StatusCode::UNAUTHORIZED,
+            OreoError::NoImported(_)
+            | OreoError::TransactionNotFound
+            | OreoError::JobNotFound(_)
+            | OreoError::NodeNotFound(_) => StatusCode::NOT_FOUND,
+            OreoError::RequestTimeout => StatusCode::REQUEST_TIMEOUT,
+            OreoError::Duplicate(_) | OreoError::Scanning(_) | OreoError::NodeAccountExists(_) => {
+                StatusCode::CONFLICT
+            }
+            OreoError::GenerateSpendProofFailed(_)
+            | OreoError::GenerateOutputProofFailed(_)
+            | OreoError::GenerateMintAssetProofFailed(_)
+            | OreoError::BalanceNotEnough => StatusCode::UNPROCESSABLE_ENTITY,
+            OreoError::QuotaExceeded(_) => StatusCode::TOO_MANY_REQUESTS,
+            OreoError::DBError
+            | OreoError::SeralizeError(_)
+            | OreoError::ParseError(_)
+            | OreoError::ProofSelfCheckFailed(_)
+            | OreoError::InternalProverError => StatusCode::INTERNAL_SERVER_ERROR,
+            OreoError::InternalRpcError(_)
+            | OreoError::NodeUnauthenticated
+            | OreoError::NodeRouteNotFound(_) => StatusCode::BAD_GATEWAY,
+            OreoError::Syncing
+            | OreoError::ProverBusy
+            | OreoError::ProverNotReady
+            | OreoError::WorkersUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Body of the error response
+    pub fn to_json(&self) -> Value {
+        let mut body = json!({
+            "code": self.code(),
+            "type": self.kind(),
+            "error": self.to_string(),
+        });
+        if let Some(failures) = self.failures() {
+            body["failures"] = json!(failures);
+        }
+        if let OreoError::InternalRpcError(e) = self {
+            body["rpc"] = json!(e);
+        }
+        if let Some(id) = request_id() {
+            body["requestId"] = json!(id);
+        }
+        body
+    }
 }
 
 impl IntoResponse for OreoError {
     fn into_response(self) -> Response {
-        let (status_code, err_msg) = match self {
-            OreoError::DBError => (StatusCode::from_u16(600).unwrap(), self.to_string()),
-            OreoError::Duplicate(_) => (StatusCode::from_u16(601).unwrap(), self.to_string()),
-            OreoError::NoImported(_) => (StatusCode::from_u16(602).unwrap(), self.to_string()),
-            OreoError::Scanning(_) => (StatusCode::from_u16(603).unwrap(), self.to_string()),
-            OreoError::Syncing => (StatusCode::from_u16(604).unwrap(), self.to_string()),
-            OreoError::InternalRpcError => (StatusCode::from_u16(605).unwrap(), self.to_string()),
-            OreoError::GenerateSpendProofFailed(_) => {
-                (StatusCode::from_u16(606).unwrap(), self.to_string())
-            }
-            OreoError::GenerateOutputProofFailed(_) => {
-                (StatusCode::from_u16(607).unwrap(), self.to_string())
-            }
-            OreoError::GenerateMintAssetProofFailed(_) => {
-                (StatusCode::from_u16(608).unwrap(), self.to_string())
-            }
-            OreoError::BalanceNotEnough => (StatusCode::from_u16(609).unwrap(), self.to_string()),
-            OreoError::BadMintRequest => (StatusCode::from_u16(610).unwrap(), self.to_string()),
-            OreoError::TransactionNotFound => {
-                (StatusCode::from_u16(611).unwrap(), self.to_string())
-            }
-            OreoError::SeralizeError(_) => (StatusCode::from_u16(612).unwrap(), self.to_string()),
-            OreoError::ParseError(_) => (StatusCode::from_u16(613).unwrap(), self.to_string()),
-        };
-        Json(json!({"code": status_code.as_u16(), "error": err_msg})).into_response()
+        (self.status(), Json(self.to_json())).into_response()
+    }
+}
+
+/// Id of the request being served, none outside of a request
+pub fn request_id() -> Option<String> {
+    REQUEST_ID.try_with(|x| x.clone()).ok()
+}
+
+/// Tag every request with the id given in `X-Request-Id`, or a random one, the id is
+/// returned in the same header and in error bodies
+pub async fn tag_request(request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|x| x.to_str().ok())
+        .filter(|x| !x.is_empty() && x.len() <= 64)
+        .map(|x| x.to_string())
+        .unwrap_or_else(|| hex::encode(rand::random::<[u8; 8]>()));
+    let mut response = REQUEST_ID.scope(id.clone(), next.run(request)).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{http::StatusCode, response::IntoResponse};
+
+    use crate::rpc_handler::RpcError;
+
+    use super::{CircuitFailure, CircuitKind, FailureReason, OreoError, REQUEST_ID};
+
+    fn failure(kind: CircuitKind, index: u32) -> CircuitFailure {
+        CircuitFailure {
+            kind,
+            index,
+            reason: FailureReason::Synthesis,
+            message: "".to_string(),
+        }
+    }
+
+    #[test]
+    fn proof_failure_should_follow_first_kind() {
+        let error = OreoError::proof_failed(vec![
+            failure(CircuitKind::Mint, 0),
+            failure(CircuitKind::Output, 2),
+            failure(CircuitKind::Output, 1),
+        ]);
+        assert_eq!(error.code(), 607);
+        let failures = error.failures().unwrap();
+        assert_eq!(failures.len(), 3);
+        assert_eq!(failures[0], failure(CircuitKind::Output, 1));
+        assert_eq!(
+            error.to_string(),
+            "Circuits can not generate proof: output `1` (synthesis), output `2` (synthesis), mint `0` (synthesis)"
+        );
+        assert_eq!(
+            OreoError::proof_failed(vec![failure(CircuitKind::Mint, 0)]).code(),
+            608
+        );
+    }
+
+    #[tokio::test]
+    async fn errors_should_carry_status_and_request_id() {
+        let error = OreoError::InternalRpcError(RpcError {
+            code: "unknown-route".to_string(),
+            status: 404,
+            message: "Route not found".to_string(),
+        });
+        assert_eq!(error.status(), StatusCode::BAD_GATEWAY);
+        let body = REQUEST_ID
+            .scope("abc".to_string(), async { error.to_json() })
+            .await;
+        assert_eq!(body["code"], 605);
+        assert_eq!(body["type"], "rpc-error");
+        assert_eq!(body["rpc"]["code"], "unknown-route");
+        assert_eq!(body["rpc"]["message"], "Route not found");
+        assert_eq!(body["requestId"], "abc");
+        assert!(error.to_json().get("requestId").is_none());
+
+        let response = OreoError::NoImported("addr".to_string()).into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
     }
 }
@@ -1,24 +1,32 @@
-use std::{net::SocketAddr, time::Duration};
+use std::{sync::atomic::Ordering, time::Duration};
 
 use anyhow::Result;
 use axum::{
     error_handling::HandleErrorLayer,
-    http::StatusCode,
+    middleware,
     routing::{get, post},
     BoxError, Router,
 };
+use config::{CoordinatorConfig, CorsConfig, ProverConfig, RequestDefaults, ServerConfig};
+use constants::NetworkProfile;
 use db_handler::{DBHandler, PgHandler};
-use rpc_handler::RpcHandler;
+use error::{tag_request, OreoError};
+use prover::{authenticate, Coordinator, ProverParams, ProverState, ProvingPool};
+use rpc_handler::RpcPool;
 use tokio::{net::TcpListener, sync::oneshot};
 use tower::{timeout::TimeoutLayer, ServiceBuilder};
-use tower_http::cors::{Any, CorsLayer};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 use tracing::{info, warn};
 use tracing_subscriber::EnvFilter;
 
 use crate::web_handlers::{
     account_status_handler, account_transaction_handler, broadcast_transaction_handler,
-    create_transaction_handler, generate_proof_handler, get_balances_handler, get_ores_handler,
-    get_transactions_handler, import_vk_handler, latest_block_handler, remove_account_handler,
+    coordinate_proof_handler, create_proven_transaction_handler, create_transaction_handler,
+    generate_proof_handler, get_balances_handler, get_ores_handler, get_proof_job_handler,
+    get_transactions_handler, get_workers_handler, import_vk_handler, latest_block_handler,
+    metrics_handler, ready_handler, register_worker_handler, remove_account_handler,
+    reorg_events_handler, require_ready, scanner_status_handler, stream_proof_handler,
+    submit_proof_job_handler, verify_proof_handler,
 };
 
 pub mod config;
@@ -26,34 +34,75 @@ pub mod constants;
 pub mod db_handler;
 pub mod error;
 pub mod orescriptions;
+pub mod prover;
 pub mod rpc_handler;
+pub mod scanner;
 pub mod web_handlers;
 
 #[derive(Debug, Clone)]
 pub struct SharedState<T: DBHandler> {
     pub db_handler: T,
-    pub rpc_handler: RpcHandler,
+    pub rpc_pool: RpcPool,
+    pub orescriptions_endpoint: String,
+    pub defaults: RequestDefaults,
+    pub network: NetworkProfile,
+    /// Compute balances from notes stored by the scanner instead of the node's wallet
+    pub local_balances: bool,
+    /// Pool `/createProvenTx` proves on, none when proving is off
+    pub pool: Option<ProvingPool>,
 }
 
 impl<T> SharedState<T>
 where
     T: DBHandler,
 {
-    pub fn new(db_handler: T, endpoint: &str) -> Self {
-        Self {
+    pub fn new(db_handler: T, config: &ServerConfig) -> Result<Self> {
+        Ok(Self {
             db_handler: db_handler,
-            rpc_handler: RpcHandler::new(endpoint.into()),
-        }
+            rpc_pool: RpcPool::new(
+                config.node_endpoints(),
+                config.transport,
+                config.timeouts.rpc_timeout(),
+                config.health_check.max_lag,
+            ),
+            orescriptions_endpoint: config.orescriptions_endpoint.clone(),
+            defaults: config.defaults.clone(),
+            network: config.network_profile()?,
+            local_balances: config.scanner.enabled,
+            pool: match config.proving.enabled {
+                true => Some(ProvingPool::new(&config.proving.prover_config())),
+                false => None,
+            },
+        })
     }
 }
 
-pub async fn run_server(
-    listen: SocketAddr,
-    rpc_server: String,
-    db_handler: PgHandler,
-) -> Result<()> {
-    let shared_state = SharedState::new(db_handler, &rpc_server);
-    let router = Router::new()
+fn cors_layer(config: &CorsConfig) -> Result<CorsLayer> {
+    let allow_origin = if config.allow_any() {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(config.origins()?)
+    };
+    Ok(CorsLayer::new()
+        .allow_methods(Any)
+        .allow_origin(allow_origin)
+        .allow_headers(Any))
+}
+
+pub async fn run_server(config: ServerConfig, db_handler: PgHandler) -> Result<()> {
+    let shared_state = SharedState::new(db_handler.clone(), &config)?;
+    info!("Server running on {}", shared_state.network.network);
+    shared_state
+        .rpc_pool
+        .spawn_health_check(config.health_check.interval());
+    if config.scanner.enabled {
+        scanner::spawn_workers(
+            db_handler,
+            shared_state.rpc_pool.clone(),
+            config.scanner.clone(),
+        );
+    }
+    let mut router = Router::new()
         .route("/import", post(import_vk_handler))
         .route("/remove", post(remove_account_handler))
         .route("/getBalances", post(get_balances_handler))
@@ -64,45 +113,100 @@ pub async fn run_server(
         .route("/accountStatus", post(account_status_handler))
         .route("/latestBlock", get(latest_block_handler))
         .route("/ores", post(get_ores_handler))
+        .route("/scannerStatus", get(scanner_status_handler))
+        .route("/reorgEvents", post(reorg_events_handler));
+    if config.proving.enabled {
+        router = router.route("/createProvenTx", post(create_proven_transaction_handler));
+    }
+    let router = router
         .with_state(shared_state)
         .layer(
             ServiceBuilder::new()
                 .layer(HandleErrorLayer::new(|_: BoxError| async {
-                    StatusCode::REQUEST_TIMEOUT
+                    OreoError::RequestTimeout
                 }))
-                .layer(TimeoutLayer::new(Duration::from_secs(30))),
+                .layer(TimeoutLayer::new(config.timeouts.request_timeout())),
         )
-        .layer(
-            CorsLayer::new()
-                .allow_methods(Any)
-                .allow_origin(Any)
-                .allow_headers(Any),
-        );
+        .layer(cors_layer(&config.cors)?)
+        .layer(middleware::from_fn(tag_request));
 
-    let listener = TcpListener::bind(&listen).await?;
-    info!("Server listening on {}", listen);
+    let listener = TcpListener::bind(&config.listen).await?;
+    info!("Server listening on {}", config.listen);
     axum::serve(listener, router).await?;
     Ok(())
 }
 
-pub async fn run_prover(listen: SocketAddr) -> Result<()> {
+pub async fn run_prover(config: ProverConfig) -> Result<()> {
+    config.validate()?;
+    let state = ProverState::new(&config)?;
     let router = Router::new()
         .route("/generateProofs", post(generate_proof_handler))
+        .route("/generateProofs/stream", post(stream_proof_handler))
+        .route("/verifyProofs", post(verify_proof_handler))
+        .route("/proofJobs", post(submit_proof_job_handler))
+        .route("/proofJobs/:id", get(get_proof_job_handler))
+        .route_layer(middleware::from_fn_with_state(state.clone(), authenticate))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_ready))
+        .route("/ready", get(ready_handler))
+        .route("/metrics", get(metrics_handler))
+        .with_state(state.clone())
         .layer(
             ServiceBuilder::new()
                 .layer(HandleErrorLayer::new(|_: BoxError| async {
-                    StatusCode::REQUEST_TIMEOUT
+                    OreoError::RequestTimeout
                 }))
-                .layer(TimeoutLayer::new(Duration::from_secs(30))),
+                .layer(TimeoutLayer::new(config.request_timeout())),
         )
         .layer(
             CorsLayer::new()
                 .allow_methods(Any)
                 .allow_origin(Any)
                 .allow_headers(Any),
-        );
-    let listener = TcpListener::bind(&listen).await?;
-    info!("Prover listening on {}", listen);
+        )
+        .layer(middleware::from_fn(tag_request));
+    let listener = TcpListener::bind(&config.listen).await?;
+    info!("Prover listening on {}", config.listen);
+    let server = tokio::spawn(async move { axum::serve(listener, router).await });
+    if config.eager_params() {
+        let loader = config.clone();
+        let loaded = tokio::task::spawn_blocking(move || ProverParams::load(&loader)).await;
+        match loaded {
+            Ok(Ok(())) => state.ready.store(true, Ordering::Release),
+            Ok(Err(e)) => {
+                server.abort();
+                return Err(e);
+            }
+            Err(e) => {
+                server.abort();
+                return Err(e.into());
+            }
+        }
+        info!("Prover ready");
+    }
+    server.await??;
+    Ok(())
+}
+
+pub async fn run_coordinator(config: CoordinatorConfig) -> Result<()> {
+    config.validate()?;
+    let coordinator = Coordinator::new(&config);
+    coordinator.spawn_health_check();
+    let router = Router::new()
+        .route("/generateProofs", post(coordinate_proof_handler))
+        .route(
+            "/workers",
+            get(get_workers_handler).post(register_worker_handler),
+        )
+        .with_state(coordinator)
+        .layer(
+            CorsLayer::new()
+                .allow_methods(Any)
+                .allow_origin(Any)
+                .allow_headers(Any),
+        )
+        .layer(middleware::from_fn(tag_request));
+    let listener = TcpListener::bind(&config.listen).await?;
+    info!("Coordinator listening on {}", config.listen);
     axum::serve(listener, router).await?;
     Ok(())
 }
@@ -142,7 +246,8 @@ pub async fn handle_signals() -> anyhow::Result<()> {
 
 #[cfg(test)]
 mod tests {
-    use axum::response::IntoResponse;
+    use std::sync::{Arc, Mutex};
+
     use bellperson::groth16::Proof;
     use blstrs::Bls12;
     use ff::{Field, PrimeField, PrimeFieldBits};
@@ -156,8 +261,11 @@ mod tests {
     use zcash_primitives::constants::VALUE_COMMITMENT_VALUE_GENERATOR;
     use zcash_primitives::sapling::{pedersen_hash, Note, ProofGenerationKey, Rseed};
 
-    use crate::error::OreoError;
-    use crate::web_handlers::abi::{GenerateProofRep, GenerateProofReq};
+    use crate::error::{CircuitFailure, CircuitKind, FailureReason, OreoError};
+    use crate::web_handlers::abi::{
+        GenerateProofRep, GenerateProofReq, ProofWithInputs, VerifyProofReq,
+    };
+    use crate::web_handlers::{generate_proofs, verify_proofs, ProofSink};
 
     fn build_spend() -> Spend {
         let mut rng = StdRng::seed_from_u64(0);
@@ -309,7 +417,7 @@ mod tests {
             mint_asset_circuits: vec![mint_asset_bytes],
         };
         let response = client
-            .post("http://127.0.0.1:10001/generate_proofs")
+            .post("http://127.0.0.1:10002/generateProofs")
             .header("Content-Type", "application/json")
             .json(&body)
             .send()
@@ -356,7 +464,7 @@ mod tests {
             mint_asset_circuits: vec![mint_asset_bytes],
         };
         let response = client
-            .post("http://127.0.0.1:10001/generate_proofs")
+            .post("http://127.0.0.1:10002/generateProofs")
             .header("Content-Type", "application/json")
             .json(&body)
             .send()
@@ -364,17 +472,75 @@ mod tests {
             .expect("failed to generate proofs");
 
         let status1 = response.status();
-        assert!(status1.is_success());
+        assert_eq!(status1.as_u16(), 422);
 
-        let error = response.json::<OreoError>().await.unwrap();
-        let error_msg = error.to_string();
+        let error: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(error["code"], 606);
+        assert_eq!(error["type"], "spend-proof-failed");
         assert_eq!(
-            error_msg,
-            OreoError::GenerateSpendProofFailed(1).to_string()
+            error["error"],
+            OreoError::proof_failed(vec![CircuitFailure {
+                kind: CircuitKind::Spend,
+                index: 1,
+                reason: FailureReason::Deserialize,
+                message: "".to_string(),
+            }])
+            .to_string()
         );
+        let failures = error["failures"].as_array().unwrap();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0]["kind"], "spend");
+        assert_eq!(failures[0]["index"], 1);
+        assert_eq!(failures[0]["reason"], "deserialize");
+    }
 
-        let response = error.into_response();
-        let status = response.status();
-        assert_eq!(status.as_u16(), 900);
+    #[test]
+    fn verify_proofs_rejects_malformed() {
+        let empty = VerifyProofReq {
+            spend_proofs: vec![],
+            output_proofs: vec![],
+            mint_asset_proofs: vec![],
+        };
+        assert!(verify_proofs(&empty).unwrap().valid);
+
+        let malformed = VerifyProofReq {
+            spend_proofs: vec![ProofWithInputs {
+                proof: vec![1, 2, 3],
+                public_inputs: vec![],
+            }],
+            ..empty
+        };
+        let error = verify_proofs(&malformed).unwrap_err();
+        assert_eq!(error.code(), 617);
+    }
+
+    #[test]
+    fn self_checked_proofs_should_verify() {
+        let mut spend_bytes = vec![];
+        build_spend().write(&mut spend_bytes).unwrap();
+        let mut output_bytes = vec![];
+        build_output().write(&mut output_bytes).unwrap();
+        let request = GenerateProofReq {
+            spend_circuits: vec![spend_bytes],
+            output_circuits: vec![output_bytes],
+            mint_asset_circuits: vec![],
+        };
+        let streamed = Arc::new(Mutex::new(vec![]));
+        let sink: ProofSink = {
+            let streamed = streamed.clone();
+            Arc::new(move |kind, index, proof: &[u8]| {
+                streamed.lock().unwrap().push((kind, index, proof.to_vec()))
+            })
+        };
+        let rep = generate_proofs(&request, true, Some(&sink)).unwrap();
+        assert_eq!(rep.spend_proofs.len(), 1);
+        assert_eq!(rep.output_proofs.len(), 1);
+        assert_eq!(
+            *streamed.lock().unwrap(),
+            vec![
+                (CircuitKind::Spend, 0, rep.spend_proofs[0].clone()),
+                (CircuitKind::Output, 0, rep.output_proofs[0].clone()),
+            ]
+        );
     }
 }
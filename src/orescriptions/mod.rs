@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    constants::{IRON_NATIVE_ASSET, OREOSRIPTIONS_ENDPOINT, OREOS_VALUE},
+    constants::{IRON_NATIVE_ASSET, OREOS_VALUE},
     rpc_handler::abi::AssetBalance,
 };
 
@@ -21,19 +21,23 @@ pub fn is_ores_local(asset: &AssetBalance) -> bool {
         && asset.unconfirmed == OREOS_VALUE.to_string()
 }
 
-pub async fn get_ores(id: &str) -> anyhow::Result<Ores> {
-    let path = format!("{}/orescription/{}", OREOSRIPTIONS_ENDPOINT, id);
-    Ok(ureq::get(&path).call()?.into_json()?)
+pub async fn get_ores(endpoint: &str, id: &str) -> anyhow::Result<Ores> {
+    let path = format!("{}/orescription/{}", endpoint, id);
+    Ok(reqwest::get(&path)
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::orescriptions::get_ores;
+    use crate::{constants::OREOSRIPTIONS_ENDPOINT, orescriptions::get_ores};
 
     #[tokio::test]
     pub async fn check_ores_should_work() {
         let asset_id = "6272e464d84761d9c6247d9d4d2feb42964a5b2a71b9b179df27bbe0730c88af";
-        let ores = get_ores(asset_id).await;
+        let ores = get_ores(OREOSRIPTIONS_ENDPOINT, asset_id).await;
         assert!(ores.is_ok());
         match ores {
             Ok(ore) => {
@@ -46,7 +50,7 @@ mod tests {
     #[tokio::test]
     pub async fn check_ores_should_fail() {
         let asset_id = "6272e464d84761d9c6247d9d4d2feb42964a5b2a71b9b179df27bbe0x30c88af";
-        let ores = get_ores(asset_id).await;
+        let ores = get_ores(OREOSRIPTIONS_ENDPOINT, asset_id).await;
         assert!(ores.is_err());
         match ores {
             Ok(ore) => {
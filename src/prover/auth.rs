@@ -0,0 +1,347 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header::CONTENT_TYPE, HeaderMap, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures::StreamExt;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::warn;
+
+use crate::{
+    config::{ProverAuthConfig, ProverClient},
+    error::OreoError,
+    scanner::unix_now,
+};
+
+use super::ProverState;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+pub const CLIENT_ID_HEADER: &str = "x-client-id";
+pub const TIMESTAMP_HEADER: &str = "x-timestamp";
+pub const SIGNATURE_HEADER: &str = "x-signature";
+
+/// Max body size read to check a signature, the default body limit of the proof routes
+const MAX_SIGNED_BODY: usize = 2 * 1024 * 1024;
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Quotas of one prover client
+#[derive(Debug)]
+struct ClientQuota {
+    client: ProverClient,
+    permits: Arc<Semaphore>,
+    /// Start of the current rate window and requests accepted in it
+    window: Mutex<(Instant, u32)>,
+}
+
+/// Concurrency permit of an authenticated client, released once every clone is dropped.
+/// Requests carry one in their extensions so proof jobs can hold it until they finish
+#[derive(Debug, Clone)]
+pub struct ClientPermit {
+    _permit: Arc<OwnedSemaphorePermit>,
+}
+
+impl From<OwnedSemaphorePermit> for ClientPermit {
+    fn from(permit: OwnedSemaphorePermit) -> Self {
+        Self {
+            _permit: Arc::new(permit),
+        }
+    }
+}
+
+/// Authenticates prover requests by api key or hmac signature and applies per client quotas
+#[derive(Debug)]
+pub struct ProverAuth {
+    clients: HashMap<String, ClientQuota>,
+    max_skew: u64,
+}
+
+impl ProverAuth {
+    pub fn new(config: &ProverAuthConfig) -> Self {
+        let clients = config
+            .clients
+            .iter()
+            .map(|client| {
+                let quota = ClientQuota {
+                    client: client.clone(),
+                    permits: Arc::new(Semaphore::new(client.max_concurrent)),
+                    window: Mutex::new((Instant::now(), 0)),
+                };
+                (client.id.clone(), quota)
+            })
+            .collect();
+        Self {
+            clients,
+            max_skew: config.max_skew,
+        }
+    }
+
+    /// Client sending `x-api-key`, none when the header is missing. Keys are compared in
+    /// constant time and every client is compared so timing reveals nothing about the keys
+    fn by_api_key(&self, headers: &HeaderMap) -> Option<Result<&ClientQuota, OreoError>> {
+        let key = headers.get(API_KEY_HEADER)?;
+        let mut found = None;
+        for quota in self.clients.values() {
+            if bool::from(quota.client.key.as_bytes().ct_eq(key.as_bytes())) {
+                found = Some(quota);
+            }
+        }
+        Some(found.ok_or(OreoError::Unauthorized))
+    }
+
+    /// Client named by `x-client-id` when the signature headers are all there and the
+    /// timestamp is fresh, checked before the body is read
+    fn signer(&self, headers: &HeaderMap) -> Result<&ClientQuota, OreoError> {
+        let quota = self
+            .clients
+            .get(header(headers, CLIENT_ID_HEADER)?)
+            .ok_or(OreoError::Unauthorized)?;
+        let skew = header(headers, TIMESTAMP_HEADER)?
+            .parse::<i64>()
+            .map_err(|_| OreoError::Unauthorized)?
+            .abs_diff(unix_now());
+        if skew > self.max_skew {
+            return Err(OreoError::Unauthorized);
+        }
+        header(headers, SIGNATURE_HEADER)?;
+        Ok(quota)
+    }
+
+    /// Client whose `x-signature` is the hex hmac-sha256 of `{x-timestamp}.{body}`
+    fn by_signature(&self, headers: &HeaderMap, body: &[u8]) -> Result<&ClientQuota, OreoError> {
+        let quota = self.signer(headers)?;
+        let timestamp = header(headers, TIMESTAMP_HEADER)?;
+        let signature =
+            hex::decode(header(headers, SIGNATURE_HEADER)?).map_err(|_| OreoError::Unauthorized)?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(quota.client.key.as_bytes())
+            .map_err(|_| OreoError::Unauthorized)?;
+        mac.update(timestamp.as_bytes());
+        mac.update(b".");
+        mac.update(body);
+        mac.verify_slice(&signature)
+            .map_err(|_| OreoError::Unauthorized)?;
+        Ok(quota)
+    }
+
+    /// Count a request against the rate of `quota` and take one of its concurrency permits,
+    /// held until the request is served
+    fn admit(&self, quota: &ClientQuota) -> Result<OwnedSemaphorePermit, OreoError> {
+        let exceeded = || OreoError::QuotaExceeded(quota.client.id.clone());
+        let permit = quota
+            .permits
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| exceeded())?;
+        let mut window = quota.window.lock().unwrap();
+        if window.0.elapsed() >= RATE_WINDOW {
+            *window = (Instant::now(), 0);
+        }
+        if window.1 >= quota.client.requests_per_minute {
+            return Err(exceeded());
+        }
+        window.1 += 1;
+        Ok(permit)
+    }
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Result<&'a str, OreoError> {
+    headers
+        .get(name)
+        .and_then(|x| x.to_str().ok())
+        .ok_or(OreoError::Unauthorized)
+}
+
+/// Route middleware rejecting requests without valid credentials or over quota,
+/// requests pass untouched when the prover runs without auth. Polling a job only needs
+/// credentials, job ids are random 128 bit values
+pub async fn authenticate(
+    State(prover): State<ProverState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(auth) = prover.auth.as_ref() else {
+        return next.run(request).await;
+    };
+    let (parts, body) = request.into_parts();
+    let (quota, body) = match auth.by_api_key(&parts.headers) {
+        Some(quota) => (quota, body),
+        None => {
+            if let Err(e) = auth.signer(&parts.headers) {
+                warn!("Prover request to {} refused: {}", parts.uri, e);
+                return e.into_response();
+            }
+            let bytes = match to_bytes(body, MAX_SIGNED_BODY).await {
+                Ok(bytes) => bytes,
+                Err(_) => {
+                    return OreoError::InvalidProofRequest(format!(
+                        "body larger than {} bytes",
+                        MAX_SIGNED_BODY
+                    ))
+                    .into_response()
+                }
+            };
+            (auth.by_signature(&parts.headers, &bytes), Body::from(bytes))
+        }
+    };
+    let quota = match quota {
+        Ok(quota) => quota,
+        Err(e) => {
+            warn!("Prover request to {} refused: {}", parts.uri, e);
+            return e.into_response();
+        }
+    };
+    if parts.method == Method::GET {
+        return next.run(Request::from_parts(parts, body)).await;
+    }
+    let permit = match auth.admit(quota) {
+        Ok(permit) => ClientPermit::from(permit),
+        Err(e) => return e.into_response(),
+    };
+    let mut request = Request::from_parts(parts, body);
+    request.extensions_mut().insert(permit.clone());
+    hold_permit(next.run(request).await, permit)
+}
+
+/// Event streams outlive their handler, their body holds `permit` until the stream ends or
+/// the client leaves. Other responses release it right away, unless a proof job took a clone
+fn hold_permit(response: Response, permit: ClientPermit) -> Response {
+    let streaming = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|x| x.to_str().ok())
+        .is_some_and(|x| x.starts_with("text/event-stream"));
+    if !streaming {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let body = Body::from_stream(body.into_data_stream().map(move |chunk| {
+        let _held = &permit;
+        chunk
+    }));
+    Response::from_parts(parts, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{
+        http::{header::CONTENT_TYPE, HeaderMap},
+        response::IntoResponse,
+        Json,
+    };
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+    use tokio::sync::Semaphore;
+
+    use crate::{
+        config::{ProverAuthConfig, ProverClient},
+        error::OreoError,
+        scanner::unix_now,
+    };
+
+    use super::{hold_permit, ClientPermit, ProverAuth};
+
+    fn auth() -> ProverAuth {
+        ProverAuth::new(&ProverAuthConfig {
+            clients: vec![ProverClient {
+                id: "wallet".to_string(),
+                key: "secret".to_string(),
+                max_concurrent: 1,
+                requests_per_minute: 2,
+            }],
+            max_skew: 300,
+        })
+    }
+
+    fn signed(timestamp: i64, body: &[u8], key: &str) -> HeaderMap {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).unwrap();
+        mac.update(format!("{}.", timestamp).as_bytes());
+        mac.update(body);
+        let mut headers = HeaderMap::new();
+        headers.insert("x-client-id", "wallet".parse().unwrap());
+        headers.insert("x-timestamp", timestamp.to_string().parse().unwrap());
+        headers.insert(
+            "x-signature",
+            hex::encode(mac.finalize().into_bytes()).parse().unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn credentials_should_be_checked() {
+        let auth = auth();
+        let mut headers = HeaderMap::new();
+        assert!(auth.by_api_key(&headers).is_none());
+        headers.insert("x-api-key", "secret".parse().unwrap());
+        assert!(auth.by_api_key(&headers).unwrap().is_ok());
+        headers.insert("x-api-key", "wrong".parse().unwrap());
+        assert_eq!(
+            auth.by_api_key(&headers).unwrap().unwrap_err(),
+            OreoError::Unauthorized
+        );
+
+        let now = unix_now();
+        assert!(auth
+            .by_signature(&signed(now, b"{}", "secret"), b"{}")
+            .is_ok());
+        assert!(auth
+            .by_signature(&signed(now, b"{}", "secret"), b"[]")
+            .is_err());
+        assert!(auth
+            .by_signature(&signed(now, b"{}", "wrong"), b"{}")
+            .is_err());
+        assert!(auth
+            .by_signature(&signed(now - 600, b"{}", "secret"), b"{}")
+            .is_err());
+
+        // refused before the body is read
+        let mut headers = signed(now, b"{}", "secret");
+        assert!(auth.signer(&headers).is_ok());
+        headers.remove("x-signature");
+        assert_eq!(auth.signer(&headers).unwrap_err(), OreoError::Unauthorized);
+        headers = signed(now, b"{}", "secret");
+        headers.insert("x-client-id", "other".parse().unwrap());
+        assert_eq!(auth.signer(&headers).unwrap_err(), OreoError::Unauthorized);
+    }
+
+    #[test]
+    fn quotas_should_be_applied() {
+        let auth = auth();
+        let quota = auth.clients.get("wallet").unwrap();
+        let permit = auth.admit(quota).unwrap();
+        assert_eq!(
+            auth.admit(quota).unwrap_err(),
+            OreoError::QuotaExceeded("wallet".to_string())
+        );
+        drop(permit);
+        let permit = auth.admit(quota).unwrap();
+        drop(permit);
+        assert!(auth.admit(quota).is_err());
+    }
+
+    #[test]
+    fn streams_should_hold_their_permit() {
+        let permits = Arc::new(Semaphore::new(1));
+        let json = Json(serde_json::json!({})).into_response();
+        let permit = || ClientPermit::from(permits.clone().try_acquire_owned().unwrap());
+        drop(hold_permit(json, permit()));
+        assert_eq!(permits.available_permits(), 1);
+
+        let stream = ([(CONTENT_TYPE, "text/event-stream")], "data: {}\n\n").into_response();
+        let stream = hold_permit(stream, permit());
+        assert_eq!(permits.available_permits(), 0);
+        drop(stream);
+        assert_eq!(permits.available_permits(), 1);
+    }
+}
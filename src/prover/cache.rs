@@ -0,0 +1,447 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use redis::{aio::MultiplexedConnection, RedisResult};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::{
+    config::ProverConfig,
+    db_handler::RedisClient,
+    error::{CircuitKind, OreoError},
+    web_handlers::{
+        abi::{GenerateProofRep, GenerateProofReq},
+        generate_proofs, ProofSink,
+    },
+};
+
+use super::{coordinator::Shard, metrics, params_fingerprint, Admission, ProvingPool};
+
+pub const REDIS_PROOF_KEY: &str = "IRONPROOF";
+
+/// In memory proofs, least recently used ones are evicted first
+#[derive(Debug, Default)]
+struct Lru {
+    /// Proof, insertion time and last use of each key
+    entries: HashMap<String, (Vec<u8>, Instant, u64)>,
+    /// Keys by last use
+    order: BTreeMap<u64, String>,
+    tick: u64,
+    capacity: usize,
+}
+
+impl Lru {
+    fn get(&mut self, key: &str, ttl: Duration) -> Option<Vec<u8>> {
+        let (proof, inserted, used) = self.entries.get_mut(key)?;
+        self.order.remove(used);
+        if inserted.elapsed() >= ttl {
+            self.entries.remove(key);
+            return None;
+        }
+        self.tick += 1;
+        *used = self.tick;
+        let proof = proof.clone();
+        self.order.insert(self.tick, key.to_string());
+        Some(proof)
+    }
+
+    fn put(&mut self, key: String, proof: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if let Some((_, _, used)) = self.entries.remove(&key) {
+            self.order.remove(&used);
+        }
+        self.tick += 1;
+        self.order.insert(self.tick, key.clone());
+        self.entries.insert(key, (proof, Instant::now(), self.tick));
+        while self.entries.len() > self.capacity {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+    }
+}
+
+/// Redis side of the cache, every request shares one multiplexed connection
+#[derive(Debug, Clone)]
+struct ProofRedis {
+    client: RedisClient,
+    /// Dropped on errors so the next call reconnects
+    con: Arc<tokio::sync::Mutex<Option<MultiplexedConnection>>>,
+}
+
+impl ProofRedis {
+    async fn con(&self) -> RedisResult<MultiplexedConnection> {
+        let mut con = self.con.lock().await;
+        if let Some(con) = con.as_ref() {
+            return Ok(con.clone());
+        }
+        let connected = self.client.get_con().await?;
+        *con = Some(connected.clone());
+        Ok(connected)
+    }
+
+    async fn reset<T>(&self, result: RedisResult<T>) -> RedisResult<T> {
+        if result.is_err() {
+            *self.con.lock().await = None;
+        }
+        result
+    }
+
+    /// Proofs of `keys` in one round trip
+    async fn get_all(&self, keys: &[String]) -> RedisResult<Vec<Option<String>>> {
+        let mut con = self.con().await?;
+        let result = redis::cmd("MGET").arg(keys).query_async(&mut con).await;
+        self.reset(result).await
+    }
+
+    /// Store every proof of `entries` for `ttl` seconds in one round trip
+    async fn put_all(&self, entries: &[(String, String)], ttl: u64) -> RedisResult<()> {
+        let mut pipe = redis::pipe();
+        for (key, proof) in entries.iter() {
+            pipe.set_ex(key, proof, ttl).ignore();
+        }
+        let mut con = self.con().await?;
+        let result = pipe.query_async(&mut con).await;
+        self.reset(result).await
+    }
+}
+
+/// Proofs by hash of their serialized circuit, kept in memory and optionally in redis
+#[derive(Debug, Clone)]
+pub struct ProofCache {
+    memory: Arc<Mutex<Lru>>,
+    ttl: Duration,
+    redis: Option<ProofRedis>,
+    /// Fingerprint of the params, proofs only verify against the params they were made with
+    params: String,
+}
+
+/// Cached proofs of a request and the circuits still to prove
+#[derive(Debug)]
+pub struct CacheLookup {
+    found: GenerateProofRep,
+    misses: Shard,
+    /// Keys of the missing circuits, spends then outputs then mints
+    miss_keys: Vec<String>,
+}
+
+/// Key of `circuit` proven with the params of fingerprint `params` in the cache
+pub fn circuit_key(params: &str, kind: CircuitKind, circuit: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(params.as_bytes());
+    hasher.update(b":");
+    hasher.update(kind.to_string().as_bytes());
+    hasher.update(b":");
+    hasher.update(circuit);
+    hex::encode(hasher.finalize())
+}
+
+impl ProofCache {
+    pub fn new(config: &ProverConfig) -> Result<Self> {
+        let redis = match &config.cache_redis {
+            Some(url) => Some(ProofRedis {
+                client: RedisClient::connect(url, 0)?,
+                con: Default::default(),
+            }),
+            None => None,
+        };
+        Ok(Self {
+            memory: Arc::new(Mutex::new(Lru {
+                capacity: config.cache_size,
+                ..Default::default()
+            })),
+            ttl: config.cache_ttl(),
+            redis,
+            params: params_fingerprint(config)?,
+        })
+    }
+
+    /// Proofs of `keys`, from memory first then from redis in one round trip
+    async fn get_all(&self, keys: &[String]) -> Vec<Option<Vec<u8>>> {
+        let mut proofs: Vec<Option<Vec<u8>>> = {
+            let mut memory = self.memory.lock().unwrap();
+            keys.iter().map(|key| memory.get(key, self.ttl)).collect()
+        };
+        let Some(redis) = self.redis.as_ref() else {
+            return proofs;
+        };
+        let missing: Vec<usize> = (0..keys.len()).filter(|i| proofs[*i].is_none()).collect();
+        if missing.is_empty() {
+            return proofs;
+        }
+        let redis_keys: Vec<String> = missing
+            .iter()
+            .map(|i| format!("{}:{}", REDIS_PROOF_KEY, keys[*i]))
+            .collect();
+        let found = match redis.get_all(&redis_keys).await {
+            Ok(found) => found,
+            Err(e) => {
+                warn!("Failed to read cached proofs from redis: {}", e);
+                return proofs;
+            }
+        };
+        let mut memory = self.memory.lock().unwrap();
+        for (i, proof) in missing.into_iter().zip(found) {
+            if let Some(proof) = proof.and_then(|x| hex::decode(x).ok()) {
+                memory.put(keys[i].clone(), proof.clone());
+                proofs[i] = Some(proof);
+            }
+        }
+        proofs
+    }
+
+    async fn put_all(&self, entries: Vec<(String, Vec<u8>)>) {
+        if let Some(redis) = self.redis.as_ref() {
+            let encoded: Vec<(String, String)> = entries
+                .iter()
+                .map(|(key, proof)| (format!("{}:{}", REDIS_PROOF_KEY, key), hex::encode(proof)))
+                .collect();
+            if let Err(e) = redis.put_all(&encoded, self.ttl.as_secs()).await {
+                warn!("Failed to cache proofs in redis: {}", e);
+            }
+        }
+        let mut memory = self.memory.lock().unwrap();
+        for (key, proof) in entries.into_iter() {
+            memory.put(key, proof);
+        }
+    }
+
+    /// Look every circuit of `request` up
+    pub async fn lookup(&self, request: &GenerateProofReq) -> CacheLookup {
+        let circuits: Vec<(CircuitKind, usize, &Vec<u8>)> = [
+            (CircuitKind::Spend, &request.spend_circuits),
+            (CircuitKind::Output, &request.output_circuits),
+            (CircuitKind::Mint, &request.mint_asset_circuits),
+        ]
+        .into_iter()
+        .flat_map(|(kind, circuits)| {
+            circuits
+                .iter()
+                .enumerate()
+                .map(move |(index, circuit)| (kind, index, circuit))
+        })
+        .collect();
+        let keys: Vec<String> = circuits
+            .iter()
+            .map(|(kind, _, circuit)| circuit_key(&self.params, *kind, circuit))
+            .collect();
+        let cached = self.get_all(&keys).await;
+
+        let mut found = GenerateProofRep {
+            spend_proofs: vec![],
+            output_proofs: vec![],
+            mint_asset_proofs: vec![],
+        };
+        let mut misses = Shard::default();
+        let mut miss_keys = vec![];
+        for ((kind, index, _), (key, proof)) in
+            circuits.into_iter().zip(keys.into_iter().zip(cached))
+        {
+            let proofs = match kind {
+                CircuitKind::Spend => &mut found.spend_proofs,
+                CircuitKind::Output => &mut found.output_proofs,
+                CircuitKind::Mint => &mut found.mint_asset_proofs,
+            };
+            match proof {
+                Some(proof) => proofs.push(proof),
+                None => {
+                    proofs.push(vec![]);
+                    misses.indices_mut(kind).push(index);
+                    miss_keys.push(key);
+                }
+            }
+        }
+        metrics()
+            .cache_hits
+            .inc_by((request.circuits() - miss_keys.len()) as u64);
+        metrics().cache_misses.inc_by(miss_keys.len() as u64);
+        CacheLookup {
+            found,
+            misses,
+            miss_keys,
+        }
+    }
+
+    /// Cache the proofs generated for the misses of `lookup` and return all proofs
+    pub async fn complete(&self, lookup: CacheLookup, rep: GenerateProofRep) -> GenerateProofRep {
+        let CacheLookup {
+            mut found,
+            misses,
+            miss_keys,
+        } = lookup;
+        let proofs = rep
+            .spend_proofs
+            .iter()
+            .chain(rep.output_proofs.iter())
+            .chain(rep.mint_asset_proofs.iter());
+        self.put_all(miss_keys.into_iter().zip(proofs.cloned()).collect())
+            .await;
+        misses.place(rep, &mut found);
+        found
+    }
+}
+
+impl CacheLookup {
+    /// Pass the cached proofs to `on_proof`
+    fn emit_found(&self, on_proof: &ProofSink) {
+        for (kind, proofs) in [
+            (CircuitKind::Spend, &self.found.spend_proofs),
+            (CircuitKind::Output, &self.found.output_proofs),
+            (CircuitKind::Mint, &self.found.mint_asset_proofs),
+        ] {
+            let misses = self.misses.indices(kind);
+            for (index, proof) in proofs.iter().enumerate() {
+                if !misses.contains(&index) {
+                    on_proof(kind, index, proof);
+                }
+            }
+        }
+    }
+
+    /// `on_proof` for the proofs of the misses, with indices of the original request
+    fn miss_sink(&self, on_proof: ProofSink) -> ProofSink {
+        let misses = self.misses.clone();
+        Arc::new(move |kind, index, proof| on_proof(kind, misses.indices(kind)[index], proof))
+    }
+}
+
+/// Generate the proofs of `request` on `pool`, reusing and filling `cache` when set.
+/// `admission` tells how the circuits get room on the pool.
+/// Each proof is passed to `on_proof` once ready, cached ones first
+pub async fn prove_cached(
+    pool: &ProvingPool,
+    cache: Option<&ProofCache>,
+    request: GenerateProofReq,
+    self_check: bool,
+    admission: Admission,
+    on_proof: Option<ProofSink>,
+) -> Result<GenerateProofRep, OreoError> {
+    let run = |request: GenerateProofReq, on_proof: Option<ProofSink>| async move {
+        let circuits = request.circuits();
+        let task = move || generate_proofs(&request, self_check, on_proof.as_ref());
+        pool.run_admitted(admission, circuits, task)
+            .await
+            .and_then(|x| x)
+    };
+    let Some(cache) = cache else {
+        return run(request, on_proof).await;
+    };
+    let lookup = cache.lookup(&request).await;
+    if let Some(on_proof) = on_proof.as_ref() {
+        lookup.emit_found(on_proof);
+    }
+    if lookup.misses.is_empty() {
+        return Ok(lookup.found);
+    }
+    let on_proof = on_proof.map(|x| lookup.miss_sink(x));
+    match run(lookup.misses.request(&request), on_proof).await {
+        Ok(rep) => Ok(cache.complete(lookup, rep).await),
+        Err(e) => match e.failures() {
+            Some(failures) => Err(OreoError::proof_failed(
+                lookup.misses.remap(failures.to_vec()),
+            )),
+            None => Err(e),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use crate::{
+        config::ProverConfig,
+        error::CircuitKind,
+        web_handlers::{
+            abi::{GenerateProofRep, GenerateProofReq},
+            ProofSink,
+        },
+    };
+
+    use super::{Lru, ProofCache};
+
+    #[test]
+    fn lru_should_evict_and_expire() {
+        let mut lru = Lru {
+            capacity: 2,
+            ..Default::default()
+        };
+        let ttl = Duration::from_secs(60);
+        lru.put("a".to_string(), vec![1]);
+        lru.put("b".to_string(), vec![2]);
+        assert_eq!(lru.get("a", ttl), Some(vec![1]));
+        lru.put("c".to_string(), vec![3]);
+        assert_eq!(lru.get("b", ttl), None);
+        assert_eq!(lru.get("a", ttl), Some(vec![1]));
+        assert_eq!(lru.get("c", Duration::ZERO), None);
+        assert_eq!(lru.entries.len(), 1);
+        assert_eq!(lru.order.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn cached_proofs_should_be_reused() {
+        let cache = ProofCache::new(&ProverConfig {
+            cache_size: 16,
+            ..Default::default()
+        })
+        .unwrap();
+        let request = GenerateProofReq {
+            spend_circuits: vec![vec![1], vec![2]],
+            output_circuits: vec![vec![1]],
+            mint_asset_circuits: vec![],
+        };
+        let lookup = cache.lookup(&request).await;
+        assert_eq!(lookup.miss_keys.len(), 3);
+        let rep = GenerateProofRep {
+            spend_proofs: vec![vec![11], vec![12]],
+            output_proofs: vec![vec![21]],
+            mint_asset_proofs: vec![],
+        };
+        let proofs = cache.complete(lookup, rep).await;
+        assert_eq!(proofs.spend_proofs, vec![vec![11], vec![12]]);
+
+        // same circuit bytes of another kind are cached apart
+        let retried = GenerateProofReq {
+            spend_circuits: vec![vec![2], vec![3]],
+            output_circuits: vec![vec![2]],
+            mint_asset_circuits: vec![],
+        };
+        let lookup = cache.lookup(&retried).await;
+        assert_eq!(lookup.misses.spends, vec![1]);
+        assert_eq!(lookup.misses.outputs, vec![0]);
+        let streamed = Arc::new(Mutex::new(vec![]));
+        let sink: ProofSink = {
+            let streamed = streamed.clone();
+            Arc::new(move |kind, index, proof: &[u8]| {
+                streamed.lock().unwrap().push((kind, index, proof.to_vec()))
+            })
+        };
+        lookup.emit_found(&sink);
+        lookup.miss_sink(sink)(CircuitKind::Spend, 0, &[13]);
+        assert_eq!(
+            *streamed.lock().unwrap(),
+            vec![
+                (CircuitKind::Spend, 0, vec![12]),
+                (CircuitKind::Spend, 1, vec![13]),
+            ]
+        );
+        let rep = GenerateProofRep {
+            spend_proofs: vec![vec![13]],
+            output_proofs: vec![vec![22]],
+            mint_asset_proofs: vec![],
+        };
+        let proofs = cache.complete(lookup, rep).await;
+        assert_eq!(proofs.spend_proofs, vec![vec![12], vec![13]]);
+        assert_eq!(proofs.output_proofs, vec![vec![22]]);
+    }
+}
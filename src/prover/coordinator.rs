@@ -0,0 +1,597 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
+use axum::http::HeaderMap;
+use futures::future::join_all;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::{
+    config::CoordinatorConfig,
+    error::{CircuitFailure, CircuitKind, FailureReason, OreoError},
+    web_handlers::abi::{GenerateProofRep, GenerateProofReq},
+};
+
+use super::API_KEY_HEADER;
+
+/// Original indices of a subset of the circuits of a request, like the ones sent to one worker
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(super) struct Shard {
+    pub spends: Vec<usize>,
+    pub outputs: Vec<usize>,
+    pub mints: Vec<usize>,
+}
+
+/// Body returned by a worker `/generateProofs`, proofs come wrapped as `{code, data}`
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum WorkerRep {
+    Proofs {
+        data: GenerateProofRep,
+    },
+    Error {
+        code: u16,
+        error: String,
+        #[serde(default)]
+        failures: Vec<CircuitFailure>,
+    },
+}
+
+/// A prover worker and whether it answered its last health check
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Worker {
+    pub url: String,
+    pub healthy: bool,
+    /// Given at startup, kept while unhealthy where registered workers are dropped
+    #[serde(skip)]
+    configured: bool,
+}
+
+/// Why a shard could not be proven
+#[derive(Debug)]
+enum ShardError {
+    /// The circuits themselves failed, trying another worker won't help
+    Circuits(Vec<CircuitFailure>),
+    /// The worker failed, the shard can be retried elsewhere
+    Worker(String),
+}
+
+impl WorkerRep {
+    /// Proofs of a shard of `circuits` circuits, or why the shard failed
+    fn into_result(self, circuits: usize) -> Result<GenerateProofRep, ShardError> {
+        match self {
+            WorkerRep::Proofs { data: rep } => {
+                let returned =
+                    rep.spend_proofs.len() + rep.output_proofs.len() + rep.mint_asset_proofs.len();
+                if returned != circuits {
+                    return Err(ShardError::Worker(format!(
+                        "{} proofs returned for {} circuits",
+                        returned, circuits
+                    )));
+                }
+                Ok(rep)
+            }
+            // a failed self check points at the worker, not the circuits
+            WorkerRep::Error { failures, .. }
+                if !failures.is_empty()
+                    && failures
+                        .iter()
+                        .all(|failure| failure.reason != FailureReason::Verification) =>
+            {
+                Err(ShardError::Circuits(failures))
+            }
+            WorkerRep::Error { code, error, .. } => {
+                Err(ShardError::Worker(format!("code {}, {}", code, error)))
+            }
+        }
+    }
+}
+
+impl Shard {
+    pub fn indices_mut(&mut self, kind: CircuitKind) -> &mut Vec<usize> {
+        match kind {
+            CircuitKind::Spend => &mut self.spends,
+            CircuitKind::Output => &mut self.outputs,
+            CircuitKind::Mint => &mut self.mints,
+        }
+    }
+
+    pub fn indices(&self, kind: CircuitKind) -> &[usize] {
+        match kind {
+            CircuitKind::Spend => &self.spends,
+            CircuitKind::Output => &self.outputs,
+            CircuitKind::Mint => &self.mints,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spends.is_empty() && self.outputs.is_empty() && self.mints.is_empty()
+    }
+
+    pub fn request(&self, request: &GenerateProofReq) -> GenerateProofReq {
+        let pick = |circuits: &[Vec<u8>], indices: &[usize]| {
+            indices.iter().map(|i| circuits[*i].clone()).collect()
+        };
+        GenerateProofReq {
+            spend_circuits: pick(&request.spend_circuits, &self.spends),
+            output_circuits: pick(&request.output_circuits, &self.outputs),
+            mint_asset_circuits: pick(&request.mint_asset_circuits, &self.mints),
+        }
+    }
+
+    /// Failures reported by a worker, with indices of the original request
+    pub fn remap(&self, failures: Vec<CircuitFailure>) -> Vec<CircuitFailure> {
+        failures
+            .into_iter()
+            .map(|mut failure| {
+                if let Some(index) = self.indices(failure.kind).get(failure.index as usize) {
+                    failure.index = *index as u32;
+                }
+                failure
+            })
+            .collect()
+    }
+
+    /// Put the proofs of this shard at the position of their circuit in `assembled`
+    pub fn place(&self, rep: GenerateProofRep, assembled: &mut GenerateProofRep) {
+        for (index, proof) in self.spends.iter().zip(rep.spend_proofs) {
+            assembled.spend_proofs[*index] = proof;
+        }
+        for (index, proof) in self.outputs.iter().zip(rep.output_proofs) {
+            assembled.output_proofs[*index] = proof;
+        }
+        for (index, proof) in self.mints.iter().zip(rep.mint_asset_proofs) {
+            assembled.mint_asset_proofs[*index] = proof;
+        }
+    }
+}
+
+/// Split the circuits of `request` in order into shards of at most `size` circuits
+fn split(request: &GenerateProofReq, size: usize) -> Vec<Shard> {
+    let circuits: Vec<(CircuitKind, usize)> = (0..request.spend_circuits.len())
+        .map(|i| (CircuitKind::Spend, i))
+        .chain((0..request.output_circuits.len()).map(|i| (CircuitKind::Output, i)))
+        .chain((0..request.mint_asset_circuits.len()).map(|i| (CircuitKind::Mint, i)))
+        .collect();
+    circuits
+        .chunks(size)
+        .map(|chunk| {
+            let mut shard = Shard::default();
+            for (kind, index) in chunk.iter() {
+                shard.indices_mut(*kind).push(*index);
+            }
+            shard
+        })
+        .collect()
+}
+
+/// Put the proofs of every shard back at the position of their circuit
+fn assemble(
+    request: &GenerateProofReq,
+    shards: &[Shard],
+    reps: Vec<GenerateProofRep>,
+) -> GenerateProofRep {
+    let mut assembled = GenerateProofRep {
+        spend_proofs: vec![vec![]; request.spend_circuits.len()],
+        output_proofs: vec![vec![]; request.output_circuits.len()],
+        mint_asset_proofs: vec![vec![]; request.mint_asset_circuits.len()],
+    };
+    for (shard, rep) in shards.iter().zip(reps) {
+        shard.place(rep, &mut assembled);
+    }
+    assembled
+}
+
+/// Fans the circuits of proof requests out to registered prover workers
+#[derive(Debug, Clone)]
+pub struct Coordinator {
+    client: Client,
+    workers: Arc<RwLock<Vec<Worker>>>,
+    next: Arc<AtomicUsize>,
+    shard_size: usize,
+    retries: usize,
+    api_key: Option<String>,
+    register_key: Option<String>,
+    health_interval: Duration,
+}
+
+impl Coordinator {
+    pub fn new(config: &CoordinatorConfig) -> Self {
+        let coordinator = Self {
+            client: Client::builder()
+                .timeout(config.worker_timeout())
+                .build()
+                .expect("failed to build worker client"),
+            workers: Arc::new(RwLock::new(vec![])),
+            next: Arc::new(AtomicUsize::new(0)),
+            shard_size: config.shard_size,
+            retries: config.retries,
+            api_key: config.api_key.clone(),
+            register_key: config.register_key.clone(),
+            health_interval: config.health_interval(),
+        };
+        for worker in config.workers.iter() {
+            coordinator.add(worker, true);
+        }
+        coordinator
+    }
+
+    fn add(&self, url: &str, configured: bool) -> bool {
+        let url = url.trim_end_matches('/').to_string();
+        let mut workers = self.workers.write().unwrap();
+        if workers.iter().any(|worker| worker.url == url) {
+            return false;
+        }
+        info!("Prover worker {} registered", url);
+        workers.push(Worker {
+            url,
+            healthy: true,
+            configured,
+        });
+        true
+    }
+
+    /// Add a worker by its base url, returns false when it was already registered. Callers
+    /// must send the register key as `x-api-key`, registration is off without one
+    pub fn register(&self, headers: &HeaderMap, url: &str) -> Result<bool, OreoError> {
+        let key = headers.get(API_KEY_HEADER).ok_or(OreoError::Unauthorized)?;
+        let expected = self.register_key.as_ref().ok_or(OreoError::Unauthorized)?;
+        if !bool::from(expected.as_bytes().ct_eq(key.as_bytes())) {
+            return Err(OreoError::Unauthorized);
+        }
+        Ok(self.add(url, false))
+    }
+
+    /// Every known worker with its health
+    pub fn list(&self) -> Vec<Worker> {
+        self.workers.read().unwrap().clone()
+    }
+
+    /// Urls of the workers shards are sent to
+    pub fn workers(&self) -> Vec<String> {
+        self.workers
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|worker| worker.healthy)
+            .map(|worker| worker.url.clone())
+            .collect()
+    }
+
+    /// Apply health check results, registered workers which failed are dropped
+    fn update_health(&self, results: Vec<(String, bool)>) {
+        let mut workers = self.workers.write().unwrap();
+        for (url, healthy) in results.into_iter() {
+            let Some(position) = workers.iter().position(|worker| worker.url == url) else {
+                continue;
+            };
+            if healthy {
+                workers[position].healthy = true;
+            } else if workers[position].configured {
+                if workers[position].healthy {
+                    warn!("Prover worker {} is down", url);
+                }
+                workers[position].healthy = false;
+            } else {
+                warn!("Prover worker {} is down, dropped", url);
+                workers.remove(position);
+            }
+        }
+    }
+
+    /// Ask every worker whether it is ready to prove
+    pub async fn check_health(&self) {
+        let urls: Vec<String> = self.list().into_iter().map(|worker| worker.url).collect();
+        let results = join_all(urls.into_iter().map(|url| async move {
+            let ready = self
+                .client
+                .get(format!("{}/ready", url))
+                .timeout(self.health_interval)
+                .send()
+                .await
+                .is_ok_and(|response| response.status().is_success());
+            (url, ready)
+        }))
+        .await;
+        self.update_health(results);
+    }
+
+    pub fn spawn_health_check(&self) -> JoinHandle<()> {
+        let coordinator = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(coordinator.health_interval);
+            loop {
+                ticker.tick().await;
+                coordinator.check_health().await;
+            }
+        })
+    }
+
+    /// Prove `request` across the workers, shards failing on a worker are retried on the
+    /// next ones and circuit failures are reported with their original indices
+    pub async fn prove(&self, request: &GenerateProofReq) -> Result<GenerateProofRep, OreoError> {
+        let workers = self.workers();
+        if workers.is_empty() && request.circuits() > 0 {
+            return Err(OreoError::WorkersUnavailable(
+                "no worker registered".to_string(),
+            ));
+        }
+        let shards = split(request, self.shard_size);
+        let results = join_all(shards.iter().map(|shard| {
+            let start = self.next.fetch_add(1, Ordering::Relaxed);
+            self.prove_shard(shard.request(request), &workers, start)
+        }))
+        .await;
+
+        let mut reps = vec![];
+        let mut failures = vec![];
+        for (shard, result) in shards.iter().zip(results) {
+            match result {
+                Ok(rep) => reps.push(rep),
+                Err(ShardError::Circuits(x)) => failures.extend(shard.remap(x)),
+                Err(ShardError::Worker(e)) => return Err(OreoError::WorkersUnavailable(e)),
+            }
+        }
+        if !failures.is_empty() {
+            return Err(OreoError::proof_failed(failures));
+        }
+        Ok(assemble(request, &shards, reps))
+    }
+
+    async fn prove_shard(
+        &self,
+        request: GenerateProofReq,
+        workers: &[String],
+        start: usize,
+    ) -> Result<GenerateProofRep, ShardError> {
+        let mut last_error = String::new();
+        for attempt in 0..=self.retries {
+            let worker = &workers[(start + attempt) % workers.len()];
+            match self.send(worker, &request).await {
+                Ok(rep) => return Ok(rep),
+                Err(ShardError::Worker(e)) => {
+                    warn!("Prover worker {} failed a shard: {}", worker, e);
+                    last_error = format!("{}: {}", worker, e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(ShardError::Worker(last_error))
+    }
+
+    async fn send(
+        &self,
+        worker: &str,
+        request: &GenerateProofReq,
+    ) -> Result<GenerateProofRep, ShardError> {
+        let mut builder = self
+            .client
+            .post(format!("{}/generateProofs", worker))
+            .json(request);
+        if let Some(key) = &self.api_key {
+            builder = builder.header(API_KEY_HEADER, key);
+        }
+        let response = builder
+            .send()
+            .await
+            .map_err(|e| ShardError::Worker(e.to_string()))?;
+        // errors come with their status and a json body, which tells circuit failures apart
+        let status = response.status();
+        let rep = match response.json::<WorkerRep>().await {
+            Ok(rep) => rep,
+            Err(_) if !status.is_success() => {
+                return Err(ShardError::Worker(format!("status {}", status)))
+            }
+            Err(e) => return Err(ShardError::Worker(e.to_string())),
+        };
+        rep.into_result(request.circuits())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        http::HeaderMap,
+        response::{IntoResponse, Response},
+        routing::post,
+        Json, Router,
+    };
+    use tokio::net::TcpListener;
+
+    use crate::{
+        config::CoordinatorConfig,
+        error::{CircuitFailure, CircuitKind, FailureReason, OreoError},
+        prover::ProofEncoding,
+        web_handlers::abi::{GenerateProofRep, GenerateProofReq},
+    };
+
+    use super::{assemble, split, Coordinator, Shard, ShardError, WorkerRep};
+
+    /// Worker echoing its circuits as proofs, spend circuits `[0xff]` fail to synthesize
+    async fn echo_worker(Json(request): Json<GenerateProofReq>) -> Response {
+        let failures: Vec<CircuitFailure> = request
+            .spend_circuits
+            .iter()
+            .enumerate()
+            .filter(|(_, circuit)| circuit.as_slice() == [0xff])
+            .map(|(index, _)| CircuitFailure {
+                kind: CircuitKind::Spend,
+                index: index as u32,
+                reason: FailureReason::Synthesis,
+                message: "bad circuit".to_string(),
+            })
+            .collect();
+        if !failures.is_empty() {
+            return OreoError::proof_failed(failures).into_response();
+        }
+        ProofEncoding::Json.encode_response(&GenerateProofRep {
+            spend_proofs: request.spend_circuits,
+            output_proofs: request.output_circuits,
+            mint_asset_proofs: request.mint_asset_circuits,
+        })
+    }
+
+    #[test]
+    fn shards_should_be_reassembled_in_order() {
+        let request = GenerateProofReq {
+            spend_circuits: vec![vec![0], vec![1], vec![2]],
+            output_circuits: vec![vec![10], vec![11]],
+            mint_asset_circuits: vec![vec![20]],
+        };
+        let shards = split(&request, 4);
+        assert_eq!(
+            shards,
+            vec![
+                Shard {
+                    spends: vec![0, 1, 2],
+                    outputs: vec![0],
+                    mints: vec![],
+                },
+                Shard {
+                    spends: vec![],
+                    outputs: vec![1],
+                    mints: vec![0],
+                },
+            ]
+        );
+        assert_eq!(shards[1].request(&request).output_circuits, vec![vec![11]]);
+
+        // workers echo their circuits as proofs
+        let reps = shards
+            .iter()
+            .map(|shard| {
+                let request = shard.request(&request);
+                GenerateProofRep {
+                    spend_proofs: request.spend_circuits,
+                    output_proofs: request.output_circuits,
+                    mint_asset_proofs: request.mint_asset_circuits,
+                }
+            })
+            .collect();
+        let assembled = assemble(&request, &shards, reps);
+        assert_eq!(assembled.spend_proofs, request.spend_circuits);
+        assert_eq!(assembled.output_proofs, request.output_circuits);
+        assert_eq!(assembled.mint_asset_proofs, request.mint_asset_circuits);
+
+        let failure = CircuitFailure {
+            kind: CircuitKind::Output,
+            index: 0,
+            reason: FailureReason::Synthesis,
+            message: "".to_string(),
+        };
+        assert_eq!(shards[1].remap(vec![failure])[0].index, 1);
+    }
+
+    #[test]
+    fn workers_should_be_registered_with_key_and_health_checked() {
+        let coordinator = Coordinator::new(&CoordinatorConfig {
+            workers: vec!["http://prover-1:10002/".to_string()],
+            register_key: Some("secret".to_string()),
+            ..Default::default()
+        });
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            coordinator.register(&headers, "http://evil:80"),
+            Err(OreoError::Unauthorized)
+        );
+        headers.insert("x-api-key", "wrong".parse().unwrap());
+        assert!(coordinator.register(&headers, "http://evil:80").is_err());
+        headers.insert("x-api-key", "secret".parse().unwrap());
+        assert_eq!(
+            coordinator.register(&headers, "http://prover-2:10002"),
+            Ok(true)
+        );
+        assert_eq!(
+            coordinator.register(&headers, "http://prover-2:10002"),
+            Ok(false)
+        );
+
+        coordinator.update_health(vec![
+            ("http://prover-1:10002".to_string(), false),
+            ("http://prover-2:10002".to_string(), false),
+        ]);
+        assert!(coordinator.workers().is_empty());
+        assert_eq!(coordinator.list().len(), 1);
+        coordinator.update_health(vec![("http://prover-1:10002".to_string(), true)]);
+        assert_eq!(coordinator.workers(), vec!["http://prover-1:10002"]);
+
+        let open = Coordinator::new(&CoordinatorConfig::default());
+        assert!(open.register(&headers, "http://prover-3:10002").is_err());
+    }
+
+    #[test]
+    fn failed_self_checks_should_be_retried() {
+        let failure = |reason| CircuitFailure {
+            kind: CircuitKind::Spend,
+            index: 0,
+            reason,
+            message: "".to_string(),
+        };
+        let rep = |reason| WorkerRep::Error {
+            code: 606,
+            error: "failed".to_string(),
+            failures: vec![failure(reason)],
+        };
+        assert!(matches!(
+            rep(FailureReason::Synthesis).into_result(1),
+            Err(ShardError::Circuits(_))
+        ));
+        assert!(matches!(
+            rep(FailureReason::Verification).into_result(1),
+            Err(ShardError::Worker(_))
+        ));
+        let proofs = WorkerRep::Proofs {
+            data: GenerateProofRep {
+                spend_proofs: vec![vec![1]],
+                output_proofs: vec![],
+                mint_asset_proofs: vec![],
+            },
+        };
+        assert!(matches!(proofs.into_result(2), Err(ShardError::Worker(_))));
+    }
+
+    #[tokio::test]
+    async fn proofs_should_be_collected_from_workers() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let router = Router::new().route("/generateProofs", post(echo_worker));
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let coordinator = Coordinator::new(&CoordinatorConfig {
+            workers: vec![url],
+            shard_size: 2,
+            ..Default::default()
+        });
+        let request = GenerateProofReq {
+            spend_circuits: vec![vec![0], vec![1], vec![2]],
+            output_circuits: vec![vec![10]],
+            mint_asset_circuits: vec![vec![20]],
+        };
+        let proofs = coordinator.prove(&request).await.unwrap();
+        assert_eq!(proofs.spend_proofs, request.spend_circuits);
+        assert_eq!(proofs.output_proofs, request.output_circuits);
+        assert_eq!(proofs.mint_asset_proofs, request.mint_asset_circuits);
+
+        // the failing circuit sits in the second shard, at index 0 there
+        let request = GenerateProofReq {
+            spend_circuits: vec![vec![0], vec![1], vec![0xff]],
+            ..request
+        };
+        match coordinator.prove(&request).await {
+            Err(e) => {
+                let failures = e.failures().unwrap();
+                assert_eq!(failures.len(), 1);
+                assert_eq!(failures[0].index, 2);
+            }
+            Ok(_) => panic!("the bad circuit should fail"),
+        }
+    }
+}
@@ -0,0 +1,331 @@
+use axum::{
+    body::Bytes,
+    http::{header, HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    error::OreoError,
+    web_handlers::abi::{GenerateProofRep, GenerateProofReq},
+};
+
+pub const BINARY_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// Wire format of proof requests and responses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofEncoding {
+    /// JSON with circuits and proofs as arrays of numbers
+    Json,
+    /// JSON with circuits and proofs as base64 strings, `application/json; encoding=base64`
+    Base64,
+    /// JSON with circuits and proofs as hex strings, `application/json; encoding=hex`
+    Hex,
+    /// Spend, output and mint sections, each a u32 count followed by u32 length prefixed
+    /// blobs, all little endian
+    Binary,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EncodedProofReq {
+    spend_circuits: Vec<String>,
+    output_circuits: Vec<String>,
+    mint_asset_circuits: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EncodedProofRep {
+    spend_proofs: Vec<String>,
+    output_proofs: Vec<String>,
+    mint_asset_proofs: Vec<String>,
+}
+
+impl ProofEncoding {
+    fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.split(';').map(|x| x.trim().to_ascii_lowercase());
+        let media = parts.next().unwrap_or_default();
+        if media == BINARY_CONTENT_TYPE {
+            return Some(ProofEncoding::Binary);
+        }
+        if media != "application/json" {
+            return None;
+        }
+        let encoding = parts.find_map(|x| x.strip_prefix("encoding=").map(|x| x.to_string()));
+        match encoding.as_deref() {
+            None => Some(ProofEncoding::Json),
+            Some("base64") => Some(ProofEncoding::Base64),
+            Some("hex") => Some(ProofEncoding::Hex),
+            Some(_) => None,
+        }
+    }
+
+    /// Encoding of the request body, plain JSON when no content type is given
+    pub fn from_content_type(headers: &HeaderMap) -> Result<Self, OreoError> {
+        match headers.get(header::CONTENT_TYPE) {
+            None => Ok(ProofEncoding::Json),
+            Some(value) => {
+                value
+                    .to_str()
+                    .ok()
+                    .and_then(Self::parse)
+                    .ok_or(OreoError::InvalidProofRequest(format!(
+                        "unsupported content type {:?}",
+                        value
+                    )))
+            }
+        }
+    }
+
+    /// First supported encoding listed in `Accept`, otherwise the request encoding
+    pub fn from_accept(headers: &HeaderMap, request: Self) -> Self {
+        headers
+            .get(header::ACCEPT)
+            .and_then(|x| x.to_str().ok())
+            .and_then(|x| x.split(',').find_map(Self::parse))
+            .unwrap_or(request)
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ProofEncoding::Json => "application/json",
+            ProofEncoding::Base64 => "application/json; encoding=base64",
+            ProofEncoding::Hex => "application/json; encoding=hex",
+            ProofEncoding::Binary => BINARY_CONTENT_TYPE,
+        }
+    }
+
+    fn encode_blob(&self, blob: &[u8]) -> String {
+        match self {
+            ProofEncoding::Hex => hex::encode(blob),
+            _ => STANDARD.encode(blob),
+        }
+    }
+
+    /// A single proof as sent in streamed events, binary falls back to base64
+    pub fn encode_proof(&self, proof: &[u8]) -> Value {
+        match self {
+            ProofEncoding::Json => json!(proof),
+            _ => Value::String(self.encode_blob(proof)),
+        }
+    }
+
+    fn decode_blob(&self, blob: &str) -> Result<Vec<u8>, OreoError> {
+        match self {
+            ProofEncoding::Hex => {
+                hex::decode(blob).map_err(|e| OreoError::InvalidProofRequest(e.to_string()))
+            }
+            _ => STANDARD
+                .decode(blob)
+                .map_err(|e| OreoError::InvalidProofRequest(e.to_string())),
+        }
+    }
+
+    fn decode_blobs(&self, blobs: Vec<String>) -> Result<Vec<Vec<u8>>, OreoError> {
+        blobs.iter().map(|x| self.decode_blob(x)).collect()
+    }
+
+    fn encode_blobs(&self, blobs: &[Vec<u8>]) -> Vec<String> {
+        blobs.iter().map(|x| self.encode_blob(x)).collect()
+    }
+
+    pub fn decode_request(&self, body: &[u8]) -> Result<GenerateProofReq, OreoError> {
+        let invalid = |e: serde_json::Error| OreoError::InvalidProofRequest(e.to_string());
+        match self {
+            ProofEncoding::Json => serde_json::from_slice(body).map_err(invalid),
+            ProofEncoding::Base64 | ProofEncoding::Hex => {
+                let request: EncodedProofReq = serde_json::from_slice(body).map_err(invalid)?;
+                Ok(GenerateProofReq {
+                    spend_circuits: self.decode_blobs(request.spend_circuits)?,
+                    output_circuits: self.decode_blobs(request.output_circuits)?,
+                    mint_asset_circuits: self.decode_blobs(request.mint_asset_circuits)?,
+                })
+            }
+            ProofEncoding::Binary => {
+                let mut reader = FrameReader { body, offset: 0 };
+                let request = GenerateProofReq {
+                    spend_circuits: reader.section()?,
+                    output_circuits: reader.section()?,
+                    mint_asset_circuits: reader.section()?,
+                };
+                if reader.offset != body.len() {
+                    return Err(OreoError::InvalidProofRequest(
+                        "trailing bytes after mint section".to_string(),
+                    ));
+                }
+                Ok(request)
+            }
+        }
+    }
+
+    pub fn encode_response(&self, proofs: &GenerateProofRep) -> Response {
+        let body = match self {
+            ProofEncoding::Json => {
+                return Json(json!({"code": 200, "data": proofs})).into_response()
+            }
+            ProofEncoding::Base64 | ProofEncoding::Hex => {
+                let data = EncodedProofRep {
+                    spend_proofs: self.encode_blobs(&proofs.spend_proofs),
+                    output_proofs: self.encode_blobs(&proofs.output_proofs),
+                    mint_asset_proofs: self.encode_blobs(&proofs.mint_asset_proofs),
+                };
+                Bytes::from(json!({"code": 200, "data": data}).to_string())
+            }
+            ProofEncoding::Binary => {
+                let mut body = vec![];
+                for section in [
+                    &proofs.spend_proofs,
+                    &proofs.output_proofs,
+                    &proofs.mint_asset_proofs,
+                ] {
+                    write_section(&mut body, section);
+                }
+                Bytes::from(body)
+            }
+        };
+        (
+            [(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static(self.content_type()),
+            )],
+            body,
+        )
+            .into_response()
+    }
+}
+
+pub fn write_section(body: &mut Vec<u8>, blobs: &[Vec<u8>]) {
+    body.extend_from_slice(&(blobs.len() as u32).to_le_bytes());
+    for blob in blobs.iter() {
+        body.extend_from_slice(&(blob.len() as u32).to_le_bytes());
+        body.extend_from_slice(blob);
+    }
+}
+
+struct FrameReader<'a> {
+    body: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> FrameReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], OreoError> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|x| *x <= self.body.len());
+        let end = end.ok_or(OreoError::InvalidProofRequest(
+            "truncated binary body".to_string(),
+        ))?;
+        let bytes = &self.body[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<usize, OreoError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    }
+
+    fn section(&mut self) -> Result<Vec<Vec<u8>>, OreoError> {
+        let count = self.u32()?;
+        let mut blobs = vec![];
+        for _ in 0..count {
+            let len = self.u32()?;
+            blobs.push(self.take(len)?.to_vec());
+        }
+        Ok(blobs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{header, HeaderMap, HeaderValue};
+
+    use crate::web_handlers::abi::GenerateProofReq;
+
+    use super::{write_section, ProofEncoding};
+
+    fn request() -> GenerateProofReq {
+        GenerateProofReq {
+            spend_circuits: vec![vec![1, 2, 3], vec![4]],
+            output_circuits: vec![],
+            mint_asset_circuits: vec![vec![5, 6]],
+        }
+    }
+
+    fn assert_same(left: GenerateProofReq, right: GenerateProofReq) {
+        assert_eq!(left.spend_circuits, right.spend_circuits);
+        assert_eq!(left.output_circuits, right.output_circuits);
+        assert_eq!(left.mint_asset_circuits, right.mint_asset_circuits);
+    }
+
+    #[test]
+    fn encoding_should_be_negotiated() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            ProofEncoding::from_content_type(&headers).unwrap(),
+            ProofEncoding::Json
+        );
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json; encoding=hex"),
+        );
+        let encoding = ProofEncoding::from_content_type(&headers).unwrap();
+        assert_eq!(encoding, ProofEncoding::Hex);
+        assert_eq!(
+            ProofEncoding::from_accept(&headers, encoding),
+            ProofEncoding::Hex
+        );
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static("text/html, application/octet-stream"),
+        );
+        assert_eq!(
+            ProofEncoding::from_accept(&headers, encoding),
+            ProofEncoding::Binary
+        );
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        assert!(ProofEncoding::from_content_type(&headers).is_err());
+    }
+
+    #[test]
+    fn requests_should_be_decoded() {
+        let json = serde_json::to_vec(&request()).unwrap();
+        assert_same(
+            ProofEncoding::Json.decode_request(&json).unwrap(),
+            request(),
+        );
+
+        let hex =
+            r#"{"spendCircuits":["010203","04"],"outputCircuits":[],"mintAssetCircuits":["0506"]}"#;
+        assert_same(
+            ProofEncoding::Hex.decode_request(hex.as_bytes()).unwrap(),
+            request(),
+        );
+
+        let base64 =
+            r#"{"spendCircuits":["AQID","BA=="],"outputCircuits":[],"mintAssetCircuits":["BQY="]}"#;
+        assert_same(
+            ProofEncoding::Base64
+                .decode_request(base64.as_bytes())
+                .unwrap(),
+            request(),
+        );
+
+        let mut binary = vec![];
+        write_section(&mut binary, &request().spend_circuits);
+        write_section(&mut binary, &request().output_circuits);
+        write_section(&mut binary, &request().mint_asset_circuits);
+        assert_same(
+            ProofEncoding::Binary.decode_request(&binary).unwrap(),
+            request(),
+        );
+        assert!(ProofEncoding::Binary
+            .decode_request(&binary[..binary.len() - 1])
+            .is_err());
+    }
+}
@@ -0,0 +1,245 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use tokio::sync::{mpsc, Mutex};
+use tracing::{debug, info, warn};
+
+use super::{prove_cached, Admission, ClientPermit, ProofCache, ProvingPool};
+use crate::{
+    config::ProverConfig,
+    error::{CircuitFailure, OreoError},
+    web_handlers::abi::{GenerateProofRep, GenerateProofReq},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProofJob {
+    pub id: String,
+    pub status: JobStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<GenerateProofRep>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<u16>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub kind: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failures: Option<Vec<CircuitFailure>>,
+    #[serde(skip)]
+    finished_at: Option<Instant>,
+}
+
+/// Queued job, with the permit of the client which submitted it when the prover runs with auth
+type QueuedJob = (String, GenerateProofReq, Option<ClientPermit>);
+type JobReceiver = Arc<Mutex<mpsc::Receiver<QueuedJob>>>;
+
+/// Bounded queue of proof jobs proven by a fixed number of workers, finished jobs are kept
+/// for `job_ttl` so clients can poll them
+#[derive(Debug, Clone)]
+pub struct ProofJobs {
+    sender: mpsc::Sender<QueuedJob>,
+    jobs: Arc<RwLock<HashMap<String, ProofJob>>>,
+    ttl: Duration,
+    self_check: bool,
+    pool: ProvingPool,
+    cache: Option<ProofCache>,
+}
+
+impl ProofJobs {
+    pub fn new(config: &ProverConfig, pool: ProvingPool, cache: Option<ProofCache>) -> Self {
+        let (sender, receiver) = mpsc::channel(config.queue_size);
+        let receiver: JobReceiver = Arc::new(Mutex::new(receiver));
+        let jobs = Self {
+            sender,
+            jobs: Arc::new(RwLock::new(HashMap::new())),
+            ttl: config.job_ttl(),
+            self_check: config.self_check,
+            pool,
+            cache,
+        };
+        for worker in 0..config.workers {
+            tokio::spawn(jobs.clone().work(worker, receiver.clone()));
+        }
+        jobs
+    }
+
+    /// Queue `request` and return the job id, fails when the queue is full. `permit` is held
+    /// until the job finishes so queued and running jobs count against the client's quota
+    pub fn submit(
+        &self,
+        request: GenerateProofReq,
+        permit: Option<ClientPermit>,
+    ) -> Result<String, OreoError> {
+        self.pool.check(request.circuits())?;
+        self.evict();
+        let id = hex::encode(rand::random::<[u8; 16]>());
+        self.jobs.write().unwrap().insert(
+            id.clone(),
+            ProofJob {
+                id: id.clone(),
+                status: JobStatus::Queued,
+                result: None,
+                code: None,
+                kind: None,
+                error: None,
+                failures: None,
+                finished_at: None,
+            },
+        );
+        if self.sender.try_send((id.clone(), request, permit)).is_err() {
+            self.jobs.write().unwrap().remove(&id);
+            return Err(OreoError::ProverBusy);
+        }
+        debug!("Proof job {} queued", id);
+        Ok(id)
+    }
+
+    /// Jobs waiting for a worker
+    pub fn queued(&self) -> usize {
+        self.jobs
+            .read()
+            .unwrap()
+            .values()
+            .filter(|x| x.status == JobStatus::Queued)
+            .count()
+    }
+
+    pub fn get(&self, id: &str) -> Result<ProofJob, OreoError> {
+        self.jobs
+            .read()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or(OreoError::JobNotFound(id.to_string()))
+    }
+
+    fn evict(&self) {
+        let ttl = self.ttl;
+        self.jobs
+            .write()
+            .unwrap()
+            .retain(|_, job| job.finished_at.is_none_or(|x| x.elapsed() < ttl));
+    }
+
+    fn update(&self, id: &str, update: impl FnOnce(&mut ProofJob)) {
+        if let Some(job) = self.jobs.write().unwrap().get_mut(id) {
+            update(job);
+        }
+    }
+
+    async fn work(self, worker: usize, receiver: JobReceiver) {
+        info!("Proof job worker {} started", worker);
+        loop {
+            let next = receiver.lock().await.recv().await;
+            let Some((id, request, permit)) = next else {
+                break;
+            };
+            self.update(&id, |job| job.status = JobStatus::Running);
+            let result = prove_cached(
+                &self.pool,
+                self.cache.as_ref(),
+                request,
+                self.self_check,
+                Admission::Wait,
+                None,
+            )
+            .await;
+            drop(permit);
+            self.update(&id, |job| {
+                match result {
+                    Ok(proofs) => {
+                        job.status = JobStatus::Done;
+                        job.result = Some(proofs);
+                    }
+                    Err(e) => {
+                        warn!("Proof job {} failed: {}", id, e);
+                        job.status = JobStatus::Failed;
+                        job.code = Some(e.code());
+                        job.kind = Some(e.kind());
+                        job.error = Some(e.to_string());
+                        job.failures = e.failures().map(|x| x.to_vec());
+                    }
+                }
+                job.finished_at = Some(Instant::now());
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use tokio::sync::Semaphore;
+
+    use crate::{
+        config::ProverConfig,
+        prover::{ClientPermit, ProvingPool},
+        web_handlers::abi::GenerateProofReq,
+    };
+
+    use super::{JobStatus, ProofJobs};
+
+    async fn wait(jobs: &ProofJobs, id: &str) -> JobStatus {
+        for _ in 0..100 {
+            let status = jobs.get(id).unwrap().status;
+            if status == JobStatus::Done || status == JobStatus::Failed {
+                return status;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("proof job not finished");
+    }
+
+    #[tokio::test]
+    async fn proof_jobs_should_work() {
+        let config = ProverConfig::default();
+        let jobs = ProofJobs::new(&config, ProvingPool::new(&config), None);
+        // the client permit is held by the job until it finishes
+        let permits = Arc::new(Semaphore::new(1));
+        let permit = ClientPermit::from(permits.clone().try_acquire_owned().unwrap());
+        let empty = jobs
+            .submit(
+                GenerateProofReq {
+                    spend_circuits: vec![],
+                    output_circuits: vec![],
+                    mint_asset_circuits: vec![],
+                },
+                Some(permit),
+            )
+            .unwrap();
+        let invalid = jobs
+            .submit(
+                GenerateProofReq {
+                    spend_circuits: vec![vec![1, 2, 3]],
+                    output_circuits: vec![],
+                    mint_asset_circuits: vec![],
+                },
+                None,
+            )
+            .unwrap();
+        assert_eq!(wait(&jobs, &empty).await, JobStatus::Done);
+        assert_eq!(permits.available_permits(), 1);
+        assert!(jobs.get(&empty).unwrap().result.is_some());
+        assert_eq!(wait(&jobs, &invalid).await, JobStatus::Failed);
+        let failed = jobs.get(&invalid).unwrap();
+        assert_eq!(failed.code, Some(606));
+        assert_eq!(failed.kind, Some("spend-proof-failed"));
+        assert_eq!(failed.failures.unwrap()[0].index, 0);
+        assert!(jobs.get("unknown").is_err());
+    }
+}
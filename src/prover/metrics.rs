@@ -0,0 +1,166 @@
+use std::sync::OnceLock;
+
+use prometheus::{
+    histogram_opts, opts, Encoder, Gauge, Histogram, HistogramVec, IntCounter, IntCounterVec,
+    IntGauge, Registry, TextEncoder,
+};
+
+use crate::error::{CircuitFailure, CircuitKind};
+
+static METRICS: OnceLock<ProverMetrics> = OnceLock::new();
+
+/// Prometheus metrics of the prover
+pub struct ProverMetrics {
+    registry: Registry,
+    /// Seconds to prove one circuit, by kind
+    pub proof_seconds: HistogramVec,
+    /// Proofs generated, by kind
+    pub proofs: IntCounterVec,
+    /// Circuits of each proof request
+    pub batch_circuits: Histogram,
+    /// Circuits which could not be proven, by kind and reason
+    pub failures: IntCounterVec,
+    /// Proof jobs waiting for a worker
+    pub jobs_queued: IntGauge,
+    /// Circuits being proven or verified on the pool
+    pub circuits_in_flight: IntGauge,
+    /// Seconds the last params load took
+    pub params_load_seconds: Gauge,
+    /// Circuits whose proof was found in the cache
+    pub cache_hits: IntCounter,
+    /// Circuits whose proof was not in the cache
+    pub cache_misses: IntCounter,
+}
+
+pub fn metrics() -> &'static ProverMetrics {
+    METRICS.get_or_init(ProverMetrics::new)
+}
+
+impl ProverMetrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+        let proof_seconds = HistogramVec::new(
+            histogram_opts!(
+                "prover_proof_seconds",
+                "Seconds to prove one circuit",
+                vec![0.1, 0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0, 32.0]
+            ),
+            &["kind"],
+        )
+        .unwrap();
+        let proofs =
+            IntCounterVec::new(opts!("prover_proofs_total", "Proofs generated"), &["kind"])
+                .unwrap();
+        let batch_circuits = Histogram::with_opts(histogram_opts!(
+            "prover_batch_circuits",
+            "Circuits of each proof request",
+            vec![1.0, 2.0, 4.0, 8.0, 16.0, 32.0, 64.0, 128.0]
+        ))
+        .unwrap();
+        let failures = IntCounterVec::new(
+            opts!(
+                "prover_failures_total",
+                "Circuits which could not be proven"
+            ),
+            &["kind", "reason"],
+        )
+        .unwrap();
+        let jobs_queued =
+            IntGauge::new("prover_jobs_queued", "Proof jobs waiting for a worker").unwrap();
+        let circuits_in_flight = IntGauge::new(
+            "prover_circuits_in_flight",
+            "Circuits being proven or verified",
+        )
+        .unwrap();
+        let params_load_seconds = Gauge::new(
+            "prover_params_load_seconds",
+            "Seconds the last params load took",
+        )
+        .unwrap();
+        let cache_hits = IntCounter::new(
+            "prover_cache_hits_total",
+            "Circuits whose proof was found in the cache",
+        )
+        .unwrap();
+        let cache_misses = IntCounter::new(
+            "prover_cache_misses_total",
+            "Circuits whose proof was not in the cache",
+        )
+        .unwrap();
+        registry.register(Box::new(proof_seconds.clone())).unwrap();
+        registry.register(Box::new(proofs.clone())).unwrap();
+        registry.register(Box::new(batch_circuits.clone())).unwrap();
+        registry.register(Box::new(failures.clone())).unwrap();
+        registry.register(Box::new(jobs_queued.clone())).unwrap();
+        registry
+            .register(Box::new(circuits_in_flight.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(params_load_seconds.clone()))
+            .unwrap();
+        registry.register(Box::new(cache_hits.clone())).unwrap();
+        registry.register(Box::new(cache_misses.clone())).unwrap();
+        Self {
+            registry,
+            proof_seconds,
+            proofs,
+            batch_circuits,
+            failures,
+            jobs_queued,
+            circuits_in_flight,
+            params_load_seconds,
+            cache_hits,
+            cache_misses,
+        }
+    }
+
+    pub fn observe_proof(&self, kind: CircuitKind, seconds: f64) {
+        let kind = kind.to_string();
+        self.proof_seconds
+            .with_label_values(&[&kind])
+            .observe(seconds);
+        self.proofs.with_label_values(&[&kind]).inc();
+    }
+
+    pub fn observe_failures(&self, failures: &[CircuitFailure]) {
+        for failure in failures.iter() {
+            self.failures
+                .with_label_values(&[&failure.kind.to_string(), &failure.reason.to_string()])
+                .inc();
+        }
+    }
+
+    /// Metrics in the prometheus text format
+    pub fn render(&self) -> String {
+        let mut buffer = vec![];
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::{CircuitFailure, CircuitKind, FailureReason};
+
+    use super::ProverMetrics;
+
+    #[test]
+    fn metrics_should_be_rendered() {
+        let metrics = ProverMetrics::new();
+        metrics.observe_proof(CircuitKind::Spend, 1.5);
+        metrics.batch_circuits.observe(3.0);
+        metrics.observe_failures(&[CircuitFailure {
+            kind: CircuitKind::Mint,
+            index: 0,
+            reason: FailureReason::Deserialize,
+            message: "".to_string(),
+        }]);
+        let rendered = metrics.render();
+        assert!(rendered.contains("prover_proofs_total{kind=\"spend\"} 1"));
+        assert!(rendered.contains("prover_proof_seconds_count{kind=\"spend\"} 1"));
+        assert!(rendered.contains("prover_batch_circuits_sum 3"));
+        assert!(rendered.contains("prover_failures_total{kind=\"mint\",reason=\"deserialize\"} 1"));
+    }
+}
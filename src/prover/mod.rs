@@ -0,0 +1,62 @@
+mod auth;
+mod cache;
+mod coordinator;
+mod encoding;
+mod jobs;
+mod metrics;
+mod params;
+mod pool;
+mod self_check;
+mod stream;
+mod transaction;
+
+pub use auth::*;
+pub use cache::*;
+pub use coordinator::*;
+pub use encoding::*;
+pub use jobs::*;
+pub use metrics::*;
+pub use params::*;
+pub use pool::*;
+pub use self_check::*;
+pub use stream::*;
+pub use transaction::*;
+
+use std::sync::{atomic::AtomicBool, Arc};
+
+use anyhow::Result;
+
+use crate::config::ProverConfig;
+
+/// State shared by prover handlers
+#[derive(Debug, Clone)]
+pub struct ProverState {
+    pub jobs: ProofJobs,
+    pub pool: ProvingPool,
+    /// Proofs by circuit hash, none when caching is off
+    pub cache: Option<ProofCache>,
+    /// Verify every generated proof before returning it
+    pub self_check: bool,
+    /// Credentials and quotas of prover clients, none when the prover is open
+    pub auth: Option<Arc<ProverAuth>>,
+    /// Params are loaded, always true when they are loaded on first use
+    pub ready: Arc<AtomicBool>,
+}
+
+impl ProverState {
+    pub fn new(config: &ProverConfig) -> Result<Self> {
+        let pool = ProvingPool::new(config);
+        let cache = match config.cache_enabled() {
+            true => Some(ProofCache::new(config)?),
+            false => None,
+        };
+        Ok(Self {
+            jobs: ProofJobs::new(config, pool.clone(), cache.clone()),
+            pool,
+            cache,
+            self_check: config.self_check,
+            auth: config.auth.as_ref().map(|x| Arc::new(ProverAuth::new(x))),
+            ready: Arc::new(AtomicBool::new(!config.eager_params())),
+        })
+    }
+}
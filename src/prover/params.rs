@@ -0,0 +1,222 @@
+use std::{collections::HashMap, fs, io, path::Path, sync::OnceLock, time::Instant};
+
+use anyhow::{anyhow, Result};
+use bellperson::groth16;
+use blstrs::Bls12;
+use ironfish_rust::sapling_bls12::SAPLING;
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use super::metrics;
+use crate::config::ProverConfig;
+
+pub const SPEND_PARAMS: &str = "sapling-spend.params";
+pub const OUTPUT_PARAMS: &str = "sapling-output.params";
+pub const MINT_PARAMS: &str = "sapling-mint.params";
+
+static PARAMS: OnceLock<ProverParams> = OnceLock::new();
+
+/// Proving parameters and prepared verifying keys of the spend, output and mint circuits
+pub struct ProverParams {
+    pub spend_params: &'static groth16::Parameters<Bls12>,
+    pub output_params: &'static groth16::Parameters<Bls12>,
+    pub mint_params: &'static groth16::Parameters<Bls12>,
+    pub spend_verifying_key: &'static groth16::PreparedVerifyingKey<Bls12>,
+    pub output_verifying_key: &'static groth16::PreparedVerifyingKey<Bls12>,
+    pub mint_verifying_key: &'static groth16::PreparedVerifyingKey<Bls12>,
+}
+
+/// Parameters used by the prover, the ones built in ironfish are loaded on first use
+/// unless others were loaded at startup
+pub fn prover_params() -> &'static ProverParams {
+    PARAMS.get_or_init(|| {
+        let start = Instant::now();
+        let params = ProverParams::builtin();
+        metrics()
+            .params_load_seconds
+            .set(start.elapsed().as_secs_f64());
+        params
+    })
+}
+
+impl ProverParams {
+    fn builtin() -> Self {
+        let sapling = &*SAPLING;
+        Self {
+            spend_params: &sapling.spend_params,
+            output_params: &sapling.output_params,
+            mint_params: &sapling.mint_params,
+            spend_verifying_key: &sapling.spend_verifying_key,
+            output_verifying_key: &sapling.output_verifying_key,
+            mint_verifying_key: &sapling.mint_verifying_key,
+        }
+    }
+
+    /// Read the three params files of `dir`, each checked against `checksums` when given
+    fn from_dir(dir: &Path, checksums: Option<&HashMap<String, String>>) -> Result<Self> {
+        let read = |name: &str| -> Result<&'static groth16::Parameters<Bls12>> {
+            let path = dir.join(name);
+            let bytes = fs::read(&path)
+                .map_err(|e| anyhow!("Failed to read params {}: {}", path.display(), e))?;
+            if let Some(checksums) = checksums {
+                let expected = checksums
+                    .get(name)
+                    .ok_or(anyhow!("No checksum for params {}", name))?;
+                let actual = hex::encode(Sha256::digest(&bytes));
+                if !actual.eq_ignore_ascii_case(expected) {
+                    return Err(anyhow!(
+                        "Checksum mismatch for params {}, expected {} got {}",
+                        path.display(),
+                        expected,
+                        actual
+                    ));
+                }
+            }
+            let params = groth16::Parameters::read(&bytes[..], false)
+                .map_err(|e| anyhow!("Failed to parse params {}: {}", path.display(), e))?;
+            Ok(Box::leak(Box::new(params)))
+        };
+        let prepare = |params: &groth16::Parameters<Bls12>| {
+            &*Box::leak(Box::new(groth16::prepare_verifying_key(&params.vk)))
+        };
+        let spend_params = read(SPEND_PARAMS)?;
+        let output_params = read(OUTPUT_PARAMS)?;
+        let mint_params = read(MINT_PARAMS)?;
+        Ok(Self {
+            spend_params,
+            output_params,
+            mint_params,
+            spend_verifying_key: prepare(spend_params),
+            output_verifying_key: prepare(output_params),
+            mint_verifying_key: prepare(mint_params),
+        })
+    }
+
+    /// Load the params configured for the prover ahead of the first request
+    pub fn load(config: &ProverConfig) -> Result<()> {
+        let start = Instant::now();
+        let params = match &config.params_dir {
+            Some(dir) => {
+                let checksums = config
+                    .params_checksums
+                    .as_ref()
+                    .map(read_checksums)
+                    .transpose()?;
+                Self::from_dir(Path::new(dir), checksums.as_ref())?
+            }
+            None => Self::builtin(),
+        };
+        PARAMS
+            .set(params)
+            .map_err(|_| anyhow!("Prover params are already loaded"))?;
+        metrics()
+            .params_load_seconds
+            .set(start.elapsed().as_secs_f64());
+        info!("Prover params loaded in {:?}", start.elapsed());
+        Ok(())
+    }
+}
+
+/// Digest of the params the prover runs with, proofs cached under other params are not
+/// served. Digests of the files come from the checksums file when given, else files are hashed
+pub fn params_fingerprint(config: &ProverConfig) -> Result<String> {
+    let Some(dir) = &config.params_dir else {
+        return Ok("builtin".to_string());
+    };
+    let checksums = config
+        .params_checksums
+        .as_ref()
+        .map(read_checksums)
+        .transpose()?;
+    let mut hasher = Sha256::new();
+    for name in [SPEND_PARAMS, OUTPUT_PARAMS, MINT_PARAMS] {
+        let digest = match checksums.as_ref().and_then(|x| x.get(name)) {
+            Some(digest) => digest.to_lowercase(),
+            None => {
+                let path = Path::new(dir).join(name);
+                let mut file = fs::File::open(&path)
+                    .map_err(|e| anyhow!("Failed to read params {}: {}", path.display(), e))?;
+                let mut file_hasher = Sha256::new();
+                io::copy(&mut file, &mut file_hasher)?;
+                hex::encode(file_hasher.finalize())
+            }
+        };
+        hasher.update(name.as_bytes());
+        hasher.update(digest.as_bytes());
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Read a `sha256sum` style file, one `<hex digest>  <file name>` per line
+pub fn read_checksums(filename: impl AsRef<Path>) -> Result<HashMap<String, String>> {
+    let path = filename.as_ref();
+    let content = fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read checksums {}: {}", path.display(), e))?;
+    let mut checksums = HashMap::new();
+    for line in content.lines().filter(|x| !x.trim().is_empty()) {
+        let mut parts = line.split_whitespace();
+        match (parts.next(), parts.next()) {
+            (Some(digest), Some(name)) => {
+                // binary mode entries are prefixed with `*`
+                checksums.insert(name.trim_start_matches('*').to_string(), digest.to_string());
+            }
+            _ => return Err(anyhow!("Invalid checksum line `{}`", line)),
+        }
+    }
+    Ok(checksums)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::config::ProverConfig;
+
+    use super::{
+        params_fingerprint, read_checksums, ProverParams, MINT_PARAMS, OUTPUT_PARAMS, SPEND_PARAMS,
+    };
+
+    #[test]
+    fn params_should_match_checksums() {
+        let dir = std::env::temp_dir().join(format!("oreo-params-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for name in [SPEND_PARAMS, OUTPUT_PARAMS, MINT_PARAMS] {
+            fs::write(dir.join(name), b"params").unwrap();
+        }
+        // sha256 of `params`
+        let digest = "a20b52fae57cc7a99c9651f1b573950fd211823e3ace3bb9c273c06430f24cd3";
+        let checksums = dir.join("SHA256SUMS");
+        fs::write(
+            &checksums,
+            format!(
+                "{}  {}\n{} *{}\n",
+                "00".repeat(32),
+                SPEND_PARAMS,
+                digest,
+                OUTPUT_PARAMS
+            ),
+        )
+        .unwrap();
+
+        let checksums = read_checksums(&checksums).unwrap();
+        assert_eq!(checksums.get(SPEND_PARAMS).unwrap(), &"00".repeat(32));
+        assert_eq!(checksums.get(OUTPUT_PARAMS).unwrap(), digest);
+        assert!(!checksums.contains_key(MINT_PARAMS));
+        let error = ProverParams::from_dir(&dir, Some(&checksums))
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.starts_with("Checksum mismatch for params"));
+
+        let mut config = ProverConfig {
+            params_dir: Some(dir.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        let hashed = params_fingerprint(&config).unwrap();
+        fs::write(dir.join(MINT_PARAMS), b"other params").unwrap();
+        assert_ne!(params_fingerprint(&config).unwrap(), hashed);
+        config.params_dir = None;
+        assert_eq!(params_fingerprint(&config).unwrap(), "builtin");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
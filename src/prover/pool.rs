@@ -0,0 +1,212 @@
+use std::{
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::Arc,
+};
+
+use tokio::sync::{oneshot, OwnedSemaphorePermit, Semaphore};
+use tracing::{error, info};
+
+use crate::{config::ProverConfig, error::OreoError};
+
+/// Room taken on the pool ahead of proving, released when dropped
+#[derive(Debug)]
+pub struct PoolReservation {
+    permit: OwnedSemaphorePermit,
+    circuits: usize,
+}
+
+/// How a request gets room on the pool
+#[derive(Debug)]
+pub enum Admission {
+    /// Wait until the pool has room
+    Wait,
+    /// Refuse as busy when the pool is saturated
+    Try,
+    /// Room reserved with [`ProvingPool::reserve`]
+    Reserved(PoolReservation),
+}
+
+/// Dedicated rayon pool proofs are generated and verified on, at most `max_in_flight`
+/// circuits are handled at once so the async runtime is never blocked by proving
+#[derive(Debug, Clone)]
+pub struct ProvingPool {
+    pool: Arc<rayon::ThreadPool>,
+    in_flight: Arc<Semaphore>,
+    max_in_flight: usize,
+}
+
+impl ProvingPool {
+    pub fn new(config: &ProverConfig) -> Self {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(config.threads)
+            .thread_name(|i| format!("prover-{}", i))
+            .build()
+            .expect("failed to build proving pool");
+        info!(
+            "Proving pool started with {} threads, {} circuits in flight",
+            pool.current_num_threads(),
+            config.max_in_flight
+        );
+        Self {
+            pool: Arc::new(pool),
+            in_flight: Arc::new(Semaphore::new(config.max_in_flight)),
+            max_in_flight: config.max_in_flight,
+        }
+    }
+
+    /// Circuits currently proven or verified
+    pub fn in_flight(&self) -> usize {
+        self.max_in_flight - self.in_flight.available_permits()
+    }
+
+    /// Refuse requests which could never fit in the pool
+    pub fn check(&self, circuits: usize) -> Result<(), OreoError> {
+        if circuits > self.max_in_flight {
+            return Err(OreoError::InvalidProofRequest(format!(
+                "{} circuits exceed the max of {} in flight",
+                circuits, self.max_in_flight
+            )));
+        }
+        Ok(())
+    }
+
+    /// Take room for `circuits` now, refused as busy when the pool is saturated
+    pub fn reserve(&self, circuits: usize) -> Result<PoolReservation, OreoError> {
+        self.check(circuits)?;
+        self.in_flight
+            .clone()
+            .try_acquire_many_owned(circuits as u32)
+            .map(|permit| PoolReservation { permit, circuits })
+            .map_err(|_| OreoError::ProverBusy)
+    }
+
+    /// Run `task` over `circuits` on the pool once admitted, a reservation must cover them
+    pub async fn run_admitted<T, F>(
+        &self,
+        admission: Admission,
+        circuits: usize,
+        task: F,
+    ) -> Result<T, OreoError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let permit = match admission {
+            Admission::Wait => {
+                self.check(circuits)?;
+                self.in_flight
+                    .clone()
+                    .acquire_many_owned(circuits as u32)
+                    .await
+                    .map_err(|_| OreoError::ProverBusy)?
+            }
+            Admission::Try => self.reserve(circuits)?.permit,
+            Admission::Reserved(reservation) => {
+                if reservation.circuits < circuits {
+                    return Err(OreoError::InternalProverError);
+                }
+                reservation.permit
+            }
+        };
+        self.spawn(permit, task).await
+    }
+
+    /// Run `task` over `circuits` on the pool, refused as busy when the pool is saturated
+    pub async fn try_run<T, F>(&self, circuits: usize, task: F) -> Result<T, OreoError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        self.run_admitted(Admission::Try, circuits, task).await
+    }
+
+    /// Run `task` over `circuits` on the pool, waiting until the pool has room for them
+    pub async fn run<T, F>(&self, circuits: usize, task: F) -> Result<T, OreoError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        self.run_admitted(Admission::Wait, circuits, task).await
+    }
+
+    async fn spawn<T, F>(&self, permit: OwnedSemaphorePermit, task: F) -> Result<T, OreoError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        // parallel iterators within `task` run on this pool too, a panic escaping a rayon
+        // spawn would abort the process so it is caught here
+        self.pool.spawn(move || {
+            let result = catch_unwind(AssertUnwindSafe(task)).map_err(|_| {
+                error!("Proving task panicked");
+                OreoError::InternalProverError
+            });
+            drop(permit);
+            let _ = sender.send(result);
+        });
+        receiver.await.map_err(|_| OreoError::InternalProverError)?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use crate::{config::ProverConfig, error::OreoError};
+
+    use super::{Admission, ProvingPool};
+
+    #[tokio::test]
+    async fn saturated_pool_should_be_busy() {
+        let pool = ProvingPool::new(&ProverConfig {
+            threads: 1,
+            max_in_flight: 2,
+            ..Default::default()
+        });
+        assert_eq!(pool.try_run(1, || 1).await.unwrap(), 1);
+        assert!(matches!(
+            pool.try_run(3, || 1).await,
+            Err(OreoError::InvalidProofRequest(_))
+        ));
+
+        let (sender, receiver) = mpsc::channel::<()>();
+        let blocked = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.try_run(2, move || receiver.recv().is_ok()).await }
+        });
+        while pool.in_flight.available_permits() > 0 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(pool.try_run(1, || 1).await, Err(OreoError::ProverBusy));
+        sender.send(()).unwrap();
+        assert!(blocked.await.unwrap().unwrap());
+        assert_eq!(pool.run(2, || 2).await.unwrap(), 2);
+
+        // a reservation holds its room until it runs
+        while pool.in_flight() > 0 {
+            tokio::task::yield_now().await;
+        }
+        let reservation = pool.reserve(2).unwrap();
+        assert_eq!(pool.reserve(1).unwrap_err(), OreoError::ProverBusy);
+        assert_eq!(
+            pool.run_admitted(Admission::Reserved(reservation), 1, || 3)
+                .await
+                .unwrap(),
+            3
+        );
+    }
+
+    #[tokio::test]
+    async fn panicking_task_should_fail_the_request_only() {
+        let pool = ProvingPool::new(&ProverConfig {
+            threads: 1,
+            max_in_flight: 1,
+            ..Default::default()
+        });
+        let panicked = pool.run(1, || -> u32 { panic!("proving failed") }).await;
+        assert_eq!(panicked, Err(OreoError::InternalProverError));
+        assert_eq!(pool.run(1, || 1).await.unwrap(), 1);
+        assert_eq!(pool.in_flight(), 0);
+    }
+}
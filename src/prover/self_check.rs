@@ -0,0 +1,147 @@
+use bellperson::{
+    groth16, Circuit, ConstraintSystem, Index, LinearCombination, SynthesisError, Variable,
+};
+use blstrs::{Bls12, Scalar};
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use tracing::error;
+
+use crate::error::{CircuitFailure, CircuitKind, FailureReason};
+
+/// Constraint system which only records the public inputs a circuit allocates,
+/// constraints and private values are skipped
+#[derive(Debug, Default)]
+pub struct InputRecorder {
+    inputs: Vec<Scalar>,
+    aux: usize,
+}
+
+impl ConstraintSystem<Scalar> for InputRecorder {
+    type Root = Self;
+
+    fn alloc<F, A, AR>(&mut self, _annotation: A, _f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.aux += 1;
+        Ok(Variable::new_unchecked(Index::Aux(self.aux - 1)))
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, _annotation: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.inputs.push(f()?);
+        // input 0 is the constant one
+        Ok(Variable::new_unchecked(Index::Input(self.inputs.len())))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, _annotation: A, _a: LA, _b: LB, _c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LB: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LC: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+    {
+    }
+
+    fn push_namespace<NR, N>(&mut self, _name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn pop_namespace(&mut self) {}
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
+
+/// Public inputs of `circuit`, as the verifier expects them
+pub fn public_inputs<C: Circuit<Scalar>>(circuit: C) -> Result<Vec<Scalar>, SynthesisError> {
+    let mut recorder = InputRecorder::default();
+    circuit.synthesize(&mut recorder)?;
+    Ok(recorder.inputs)
+}
+
+/// Verify each proof against the public inputs of the circuit it was generated from,
+/// circuits are read again from `circuits` since proving consumed them
+pub fn self_check<C: Circuit<Scalar>>(
+    kind: CircuitKind,
+    circuits: &[Vec<u8>],
+    proofs: &[Vec<u8>],
+    read: impl Fn(&[u8]) -> std::io::Result<C> + Sync,
+    key: impl FnOnce() -> &'static groth16::PreparedVerifyingKey<Bls12>,
+) -> Vec<CircuitFailure> {
+    if proofs.is_empty() {
+        return vec![];
+    }
+    let key = key();
+    circuits
+        .par_iter()
+        .zip(proofs.par_iter())
+        .enumerate()
+        .filter_map(|(index, (circuit, proof))| {
+            let failure = |message: String| CircuitFailure {
+                kind,
+                index: index as u32,
+                reason: FailureReason::Verification,
+                message,
+            };
+            let inputs = read(&circuit[..])
+                .map_err(|e| e.to_string())
+                .and_then(|x| public_inputs(x).map_err(|e| e.to_string()))
+                .map_err(failure);
+            let proof =
+                groth16::Proof::<Bls12>::read(&proof[..]).map_err(|e| failure(e.to_string()));
+            let result = match (inputs, proof) {
+                (Ok(inputs), Ok(proof)) => match groth16::verify_proof(key, &proof, &inputs) {
+                    Ok(true) => Ok(()),
+                    Ok(false) => Err(failure("proof does not verify".to_string())),
+                    Err(e) => Err(failure(e.to_string())),
+                },
+                (Err(e), _) | (_, Err(e)) => Err(e),
+            };
+            result.err()
+        })
+        .inspect(|x| error!("self check of {} proof {} failed", x.kind, x.index))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use bellperson::{Circuit, ConstraintSystem, SynthesisError};
+    use blstrs::Scalar;
+
+    use super::public_inputs;
+
+    struct Square(u64);
+
+    impl Circuit<Scalar> for Square {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let root = cs.alloc(|| "root", || Ok(Scalar::from(self.0)))?;
+            let square = cs.alloc_input(|| "square", || Ok(Scalar::from(self.0 * self.0)))?;
+            cs.enforce(
+                || "square",
+                |lc| lc + root,
+                |lc| lc + root,
+                |lc| lc + square,
+            );
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn public_inputs_should_be_recorded() {
+        assert_eq!(public_inputs(Square(3)).unwrap(), vec![Scalar::from(9)]);
+    }
+}
@@ -0,0 +1,136 @@
+use std::{
+    convert::Infallible,
+    sync::{Arc, Mutex},
+};
+
+use axum::response::sse::Event;
+use futures::{channel::mpsc, Stream, StreamExt};
+use serde_json::json;
+
+use crate::{
+    error::{CircuitKind, OreoError},
+    web_handlers::{abi::GenerateProofReq, ProofSink},
+};
+
+use super::{prove_cached, Admission, PoolReservation, ProofEncoding, ProverState};
+
+/// Event of a streamed proof request
+#[derive(Debug, PartialEq)]
+pub enum ProofEvent {
+    /// A proof is ready, `index` is the position of its circuit in the request
+    Proof {
+        kind: CircuitKind,
+        index: usize,
+        proof: Vec<u8>,
+    },
+    /// Proofs ready out of the circuits of the request
+    Progress {
+        done: usize,
+        total: usize,
+    },
+    /// Every proof was sent
+    Done {
+        total: usize,
+    },
+    Failed(OreoError),
+}
+
+impl ProofEvent {
+    pub fn into_event(self, encoding: ProofEncoding) -> Event {
+        let (name, data) = match self {
+            ProofEvent::Proof { kind, index, proof } => (
+                "proof",
+                json!({"kind": kind, "index": index, "proof": encoding.encode_proof(&proof)}),
+            ),
+            ProofEvent::Progress { done, total } => {
+                ("progress", json!({"done": done, "total": total}))
+            }
+            ProofEvent::Done { total } => ("done", json!({"total": total})),
+            ProofEvent::Failed(e) => ("error", e.to_json()),
+        };
+        Event::default().event(name).data(data.to_string())
+    }
+}
+
+/// Sink sending each proof then the progress of the request to `sender`
+fn progress_sink(sender: mpsc::UnboundedSender<ProofEvent>, total: usize) -> ProofSink {
+    // proofs complete on several threads, progress is sent in order
+    let done = Mutex::new(0);
+    Arc::new(move |kind, index, proof| {
+        let mut done = done.lock().unwrap();
+        *done += 1;
+        // the client may be gone, proving goes on for the cache
+        let _ = sender.unbounded_send(ProofEvent::Proof {
+            kind,
+            index,
+            proof: proof.to_vec(),
+        });
+        let _ = sender.unbounded_send(ProofEvent::Progress { done: *done, total });
+    })
+}
+
+/// Prove `request` in the background on the room of `reservation`, its proofs are streamed
+/// as they complete and the stream ends with a `done` or `error` event
+pub fn stream_proofs(
+    prover: ProverState,
+    request: GenerateProofReq,
+    reservation: PoolReservation,
+    encoding: ProofEncoding,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    let (sender, receiver) = mpsc::unbounded();
+    let total = request.circuits();
+    let _ = sender.unbounded_send(ProofEvent::Progress { done: 0, total });
+    let sink = progress_sink(sender.clone(), total);
+    tokio::spawn(async move {
+        let result = prove_cached(
+            &prover.pool,
+            prover.cache.as_ref(),
+            request,
+            prover.self_check,
+            Admission::Reserved(reservation),
+            Some(sink),
+        )
+        .await;
+        let _ = sender.unbounded_send(match result {
+            Ok(_) => ProofEvent::Done { total },
+            Err(e) => ProofEvent::Failed(e),
+        });
+    });
+    receiver.map(move |event| Ok(event.into_event(encoding)))
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{channel::mpsc, StreamExt};
+
+    use crate::error::CircuitKind;
+
+    use super::{progress_sink, ProofEvent};
+
+    #[tokio::test]
+    async fn proofs_should_be_followed_by_progress() {
+        let (sender, receiver) = mpsc::unbounded();
+        let sink = progress_sink(sender, 2);
+        sink(CircuitKind::Output, 1, &[7]);
+        sink(CircuitKind::Spend, 0, &[8]);
+        drop(sink);
+        let events: Vec<ProofEvent> = receiver.collect().await;
+        assert_eq!(
+            events,
+            vec![
+                ProofEvent::Proof {
+                    kind: CircuitKind::Output,
+                    index: 1,
+                    proof: vec![7],
+                },
+                ProofEvent::Progress { done: 1, total: 2 },
+                ProofEvent::Proof {
+                    kind: CircuitKind::Spend,
+                    index: 0,
+                    proof: vec![8],
+                },
+                ProofEvent::Progress { done: 2, total: 2 },
+            ]
+        );
+    }
+}
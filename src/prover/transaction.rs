@@ -0,0 +1,732 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Read},
+};
+
+use ff::Field;
+use group::GroupEncoding;
+use ironfish_rust::{assets::asset::Asset, PublicAddress};
+use ironfish_zkp::{
+    primitives::ValueCommitment,
+    proofs::{MintAsset, Output, Spend},
+    util::asset_hash_to_point,
+};
+use rand::{thread_rng, RngCore};
+use zcash_primitives::{constants::PROOF_GENERATION_KEY_GENERATOR, sapling::ProofGenerationKey};
+
+use crate::{constants::IRON_NATIVE_ASSET, error::OreoError, web_handlers::abi::GenerateProofReq};
+
+/// Bytes of a serialized note
+pub const NOTE_SIZE: usize = 168;
+
+/// Longest mint name or metadata accepted in a raw transaction
+const MAX_MINT_STRING: u64 = 1024;
+
+/// Note as serialized by ironfish, owner and sender are public addresses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawNote {
+    pub owner: [u8; 32],
+    pub asset_id: [u8; 32],
+    pub value: u64,
+    pub randomness: [u8; 32],
+    pub memo: [u8; 32],
+    pub sender: [u8; 32],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawSpend {
+    pub note: RawNote,
+    pub tree_size: u32,
+    pub root_hash: [u8; 32],
+    /// Sibling hash of each level, with true when the sibling is on the right
+    pub auth_path: Vec<(bool, [u8; 32])>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawMint {
+    /// Only serialized from version 2, the account creates the asset before
+    pub creator: Option<[u8; 32]>,
+    pub name: String,
+    pub metadata: String,
+    pub value: u64,
+    pub transfer_ownership_to: Option<[u8; 32]>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawBurn {
+    pub asset_id: [u8; 32],
+    pub value: u64,
+}
+
+/// Unsigned transaction returned by the node's `wallet/createTransaction`, before
+/// the node adds change, builds descriptions and proves them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawTransaction {
+    pub version: u8,
+    pub fee: u64,
+    pub spends: Vec<RawSpend>,
+    pub outputs: Vec<RawNote>,
+    pub mints: Vec<RawMint>,
+    pub burns: Vec<RawBurn>,
+    pub expiration: u32,
+}
+
+/// Output note with the randomness its description is built with
+#[derive(Debug, Clone)]
+pub struct OutputSecrets {
+    pub note: RawNote,
+    pub value_commitment_randomness: jubjub::Fr,
+    pub esk: jubjub::Fr,
+}
+
+/// Randomness the circuits of a raw transaction are built with, the client needs it
+/// with the proofs to assemble and sign the transaction
+#[derive(Debug)]
+pub struct TransactionSecrets {
+    /// Randomizes the spending key, shared by every description
+    pub public_key_randomness: jubjub::Fr,
+    /// Value commitment randomness of each spend
+    pub spends: Vec<jubjub::Fr>,
+    /// Outputs of the raw transaction followed by the change notes
+    pub outputs: Vec<OutputSecrets>,
+}
+
+impl RawNote {
+    fn read<R: Read>(reader: &mut R) -> io::Result<Self> {
+        Ok(Self {
+            owner: read_array(reader)?,
+            asset_id: read_array(reader)?,
+            value: read_u64(reader)?,
+            randomness: read_array(reader)?,
+            memo: read_array(reader)?,
+            sender: read_array(reader)?,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(NOTE_SIZE);
+        bytes.extend_from_slice(&self.owner);
+        bytes.extend_from_slice(&self.asset_id);
+        bytes.extend_from_slice(&self.value.to_le_bytes());
+        bytes.extend_from_slice(&self.randomness);
+        bytes.extend_from_slice(&self.memo);
+        bytes.extend_from_slice(&self.sender);
+        bytes
+    }
+}
+
+impl RawTransaction {
+    /// Read a raw transaction of version 1 or 2, trailing bytes are refused
+    pub fn read(bytes: &[u8]) -> Result<Self, OreoError> {
+        let mut reader = bytes;
+        let transaction = Self::read_from(&mut reader)
+            .map_err(|e| OreoError::ParseError(format!("raw transaction, {}", e)))?;
+        if !reader.is_empty() {
+            return Err(OreoError::ParseError(format!(
+                "raw transaction, {} trailing bytes",
+                reader.len()
+            )));
+        }
+        Ok(transaction)
+    }
+
+    fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let version = read_array::<1, R>(reader)?[0];
+        if !(1..=2).contains(&version) {
+            return Err(invalid(format!("unsupported version {}", version)));
+        }
+        let fee = read_u64(reader)?;
+
+        let mut spends = vec![];
+        for _ in 0..read_u64(reader)? {
+            let note = RawNote::read(reader)?;
+            let tree_size = u32::from_le_bytes(read_array(reader)?);
+            let root_hash = read_array(reader)?;
+            let mut auth_path = vec![];
+            for _ in 0..read_u64(reader)? {
+                let is_right = match read_array::<1, R>(reader)?[0] {
+                    0 => false,
+                    1 => true,
+                    side => return Err(invalid(format!("invalid witness side {}", side))),
+                };
+                auth_path.push((is_right, read_array(reader)?));
+            }
+            spends.push(RawSpend {
+                note,
+                tree_size,
+                root_hash,
+                auth_path,
+            });
+        }
+
+        let mut outputs = vec![];
+        for _ in 0..read_u64(reader)? {
+            outputs.push(RawNote::read(reader)?);
+        }
+
+        let mut mints = vec![];
+        for _ in 0..read_u64(reader)? {
+            let creator = match version >= 2 {
+                true => Some(read_array(reader)?),
+                false => None,
+            };
+            let name = read_var_string(reader)?;
+            let metadata = read_var_string(reader)?;
+            let value = read_u64(reader)?;
+            let transfer_ownership_to = match version >= 2 && read_array::<1, R>(reader)?[0] != 0 {
+                true => Some(read_array(reader)?),
+                false => None,
+            };
+            mints.push(RawMint {
+                creator,
+                name,
+                metadata,
+                value,
+                transfer_ownership_to,
+            });
+        }
+
+        let mut burns = vec![];
+        for _ in 0..read_u64(reader)? {
+            burns.push(RawBurn {
+                asset_id: read_array(reader)?,
+                value: read_u64(reader)?,
+            });
+        }
+
+        let expiration = u32::from_le_bytes(read_array(reader)?);
+        Ok(Self {
+            version,
+            fee,
+            spends,
+            outputs,
+            mints,
+            burns,
+            expiration,
+        })
+    }
+
+    /// Ids of the minted assets, created by `account` when the mint has no creator
+    pub fn mint_asset_ids(&self, account: [u8; 32]) -> Result<Vec<[u8; 32]>, OreoError> {
+        let mut ids = vec![];
+        for mint in self.mints.iter() {
+            let creator = PublicAddress::new(&mint.creator.unwrap_or(account))
+                .map_err(|e| OreoError::ParseError(format!("mint creator, {}", e)))?;
+            let asset = Asset::new(creator, &mint.name, &mint.metadata)
+                .map_err(|e| OreoError::ParseError(format!("mint asset, {}", e)))?;
+            ids.push(*asset.id().as_bytes());
+        }
+        Ok(ids)
+    }
+
+    /// Change notes sent back to `account`, one per asset spent or minted and not fully
+    /// used by the outputs, burns and fee, like ironfish does when building transactions
+    pub fn change_notes(
+        &self,
+        account: [u8; 32],
+        mint_asset_ids: &[[u8; 32]],
+    ) -> Result<Vec<RawNote>, OreoError> {
+        let native = native_asset_id();
+        let mut balances: BTreeMap<[u8; 32], i128> = BTreeMap::new();
+        for spend in self.spends.iter() {
+            *balances.entry(spend.note.asset_id).or_default() += spend.note.value as i128;
+        }
+        for output in self.outputs.iter() {
+            *balances.entry(output.asset_id).or_default() -= output.value as i128;
+        }
+        for (mint, asset_id) in self.mints.iter().zip(mint_asset_ids.iter()) {
+            *balances.entry(*asset_id).or_default() += mint.value as i128;
+        }
+        for burn in self.burns.iter() {
+            *balances.entry(burn.asset_id).or_default() -= burn.value as i128;
+        }
+        *balances.entry(native).or_default() -= self.fee as i128;
+
+        let mut rng = thread_rng();
+        let mut notes = vec![];
+        for (asset_id, balance) in balances.into_iter() {
+            let value = u64::try_from(balance).map_err(|_| OreoError::BalanceNotEnough)?;
+            if value == 0 {
+                continue;
+            }
+            notes.push(RawNote {
+                owner: account,
+                asset_id,
+                value,
+                randomness: jubjub::Fr::random(&mut rng).to_bytes(),
+                memo: [0; 32],
+                sender: account,
+            });
+        }
+        Ok(notes)
+    }
+
+    /// Spend, output and mint circuits of the transaction proven with `key`, the outputs
+    /// are followed by the change sent back to `account`
+    pub fn circuits(
+        &self,
+        key: &ProofGenerationKey,
+        account: [u8; 32],
+    ) -> Result<(GenerateProofReq, TransactionSecrets), OreoError> {
+        let mint_asset_ids = self.mint_asset_ids(account)?;
+        let mut outputs = self.outputs.clone();
+        outputs.extend(self.change_notes(account, &mint_asset_ids)?);
+
+        let mut rng = thread_rng();
+        let ar = jubjub::Fr::random(&mut rng);
+        let mut request = GenerateProofReq {
+            spend_circuits: vec![],
+            output_circuits: vec![],
+            mint_asset_circuits: vec![],
+        };
+
+        let mut spends = vec![];
+        for spend in self.spends.iter() {
+            let value_commitment = value_commitment(&spend.note, &mut rng)?;
+            let mut auth_path = vec![];
+            for (is_right, hash) in spend.auth_path.iter() {
+                auth_path.push(Some((merkle_hash(hash)?, *is_right)));
+            }
+            let circuit = Spend {
+                value_commitment: Some(value_commitment.clone()),
+                proof_generation_key: Some(key.clone()),
+                payment_address: Some(address(&spend.note.owner)?),
+                commitment_randomness: Some(scalar(&spend.note.randomness)?),
+                ar: Some(ar),
+                auth_path,
+                anchor: Some(merkle_hash(&spend.root_hash)?),
+                sender_address: Some(address(&spend.note.sender)?),
+            };
+            request
+                .spend_circuits
+                .push(write_circuit(|x| circuit.write(x))?);
+            spends.push(value_commitment.randomness);
+        }
+
+        let mut secrets = vec![];
+        for note in outputs.into_iter() {
+            let value_commitment = value_commitment(&note, &mut rng)?;
+            let esk = jubjub::Fr::random(&mut rng);
+            let circuit = Output {
+                value_commitment: Some(value_commitment.clone()),
+                payment_address: Some(address(&note.owner)?),
+                commitment_randomness: Some(scalar(&note.randomness)?),
+                esk: Some(esk),
+                asset_id: note.asset_id,
+                proof_generation_key: Some(key.clone()),
+                ar: Some(ar),
+            };
+            request
+                .output_circuits
+                .push(write_circuit(|x| circuit.write(x))?);
+            secrets.push(OutputSecrets {
+                note,
+                value_commitment_randomness: value_commitment.randomness,
+                esk,
+            });
+        }
+
+        for _ in self.mints.iter() {
+            let circuit = MintAsset {
+                proof_generation_key: Some(key.clone()),
+                public_key_randomness: Some(ar),
+            };
+            request
+                .mint_asset_circuits
+                .push(write_circuit(|x| circuit.write(x))?);
+        }
+
+        let secrets = TransactionSecrets {
+            public_key_randomness: ar,
+            spends,
+            outputs: secrets,
+        };
+        Ok((request, secrets))
+    }
+}
+
+/// Proof generation key given as hex of `ak` and `nsk`, it must belong to the account
+/// with `view_key`, the hex of `ak` and `nk`
+pub fn proof_generation_key(key: &str, view_key: &str) -> Result<ProofGenerationKey, OreoError> {
+    let invalid_key = |reason: &str| OreoError::InvalidProofGenerationKey(reason.to_string());
+    let bytes: [u8; 64] = hex::decode(key)
+        .ok()
+        .and_then(|x| x.try_into().ok())
+        .ok_or(invalid_key("expected 64 bytes hex"))?;
+    let ak: Option<jubjub::SubgroupPoint> =
+        jubjub::SubgroupPoint::from_bytes(&bytes[..32].try_into().unwrap()).into();
+    let nsk: Option<jubjub::Fr> = jubjub::Fr::from_bytes(&bytes[32..].try_into().unwrap()).into();
+    let (Some(ak), Some(nsk)) = (ak, nsk) else {
+        return Err(invalid_key("not a valid key"));
+    };
+    let nk = *PROOF_GENERATION_KEY_GENERATOR * nsk;
+    let view_key = hex::decode(view_key).map_err(|e| OreoError::ParseError(e.to_string()))?;
+    if view_key.len() != 64
+        || view_key[..32] != ak.to_bytes()[..]
+        || view_key[32..] != nk.to_bytes()[..]
+    {
+        return Err(invalid_key("it doesn't belong to the account"));
+    }
+    Ok(ProofGenerationKey { ak, nsk })
+}
+
+fn native_asset_id() -> [u8; 32] {
+    let mut id = [0; 32];
+    hex::decode_to_slice(IRON_NATIVE_ASSET, &mut id).unwrap();
+    id
+}
+
+fn value_commitment(note: &RawNote, rng: &mut impl RngCore) -> Result<ValueCommitment, OreoError> {
+    let asset_generator = asset_hash_to_point(&note.asset_id).ok_or(OreoError::ParseError(
+        format!("asset id {}", hex::encode(note.asset_id)),
+    ))?;
+    Ok(ValueCommitment {
+        value: note.value,
+        randomness: jubjub::Fr::random(rng),
+        asset_generator,
+    })
+}
+
+fn address(bytes: &[u8; 32]) -> Result<jubjub::SubgroupPoint, OreoError> {
+    let point: Option<jubjub::SubgroupPoint> = jubjub::SubgroupPoint::from_bytes(bytes).into();
+    point.ok_or(OreoError::ParseError(format!(
+        "public address {}",
+        hex::encode(bytes)
+    )))
+}
+
+fn scalar(bytes: &[u8; 32]) -> Result<jubjub::Fr, OreoError> {
+    let scalar: Option<jubjub::Fr> = jubjub::Fr::from_bytes(bytes).into();
+    scalar.ok_or(OreoError::ParseError(format!(
+        "note randomness {}",
+        hex::encode(bytes)
+    )))
+}
+
+fn merkle_hash(bytes: &[u8; 32]) -> Result<blstrs::Scalar, OreoError> {
+    let hash: Option<blstrs::Scalar> = blstrs::Scalar::from_bytes_le(bytes).into();
+    hash.ok_or(OreoError::ParseError(format!(
+        "merkle hash {}",
+        hex::encode(bytes)
+    )))
+}
+
+fn write_circuit(write: impl FnOnce(&mut Vec<u8>) -> io::Result<()>) -> Result<Vec<u8>, OreoError> {
+    let mut bytes = vec![];
+    write(&mut bytes).map_err(|e| OreoError::SeralizeError(e.to_string()))?;
+    Ok(bytes)
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_array<const N: usize, R: Read>(reader: &mut R) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    Ok(u64::from_le_bytes(read_array(reader)?))
+}
+
+/// Utf8 string prefixed with its length as a bitcoin style var int
+fn read_var_string<R: Read>(reader: &mut R) -> io::Result<String> {
+    let len = match read_array::<1, R>(reader)?[0] {
+        0xfd => u16::from_le_bytes(read_array(reader)?) as u64,
+        0xfe => u32::from_le_bytes(read_array(reader)?) as u64,
+        0xff => read_u64(reader)?,
+        len => len as u64,
+    };
+    if len > MAX_MINT_STRING {
+        return Err(invalid(format!("string of {} bytes", len)));
+    }
+    let mut bytes = vec![0; len as usize];
+    reader.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|e| invalid(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use ff::Field;
+    use group::GroupEncoding;
+    use ironfish_zkp::proofs::{MintAsset, Output, Spend};
+    use rand::{rngs::StdRng, SeedableRng};
+    use zcash_primitives::{
+        constants::PROOF_GENERATION_KEY_GENERATOR, sapling::ProofGenerationKey,
+    };
+
+    use crate::error::OreoError;
+
+    use super::{
+        merkle_hash, native_asset_id, proof_generation_key, RawBurn, RawMint, RawNote, RawSpend,
+        RawTransaction, NOTE_SIZE,
+    };
+
+    /// Proof generation key from a fixed seed, with the hex of the key and of its view key
+    fn known_key() -> (ProofGenerationKey, String, String) {
+        let mut rng = StdRng::seed_from_u64(7);
+        let ak = *PROOF_GENERATION_KEY_GENERATOR * jubjub::Fr::random(&mut rng);
+        let nsk = jubjub::Fr::random(&mut rng);
+        let nk = *PROOF_GENERATION_KEY_GENERATOR * nsk;
+        let key = hex::encode([ak.to_bytes(), nsk.to_bytes()].concat());
+        let view_key = hex::encode([ak.to_bytes(), nk.to_bytes()].concat());
+        (ProofGenerationKey { ak, nsk }, key, view_key)
+    }
+
+    /// Public address of a random point, valid as note owner and mint creator
+    fn random_address(rng: &mut StdRng) -> [u8; 32] {
+        (*PROOF_GENERATION_KEY_GENERATOR * jubjub::Fr::random(rng)).to_bytes()
+    }
+
+    fn note(asset_id: [u8; 32], value: u64) -> RawNote {
+        RawNote {
+            owner: [1; 32],
+            asset_id,
+            value,
+            randomness: [2; 32],
+            memo: [3; 32],
+            sender: [4; 32],
+        }
+    }
+
+    fn raw_transaction(version: u8) -> Vec<u8> {
+        let mut bytes = vec![version];
+        bytes.extend_from_slice(&2u64.to_le_bytes());
+        // one spend with a path of two levels
+        bytes.extend_from_slice(&1u64.to_le_bytes());
+        bytes.extend(note(native_asset_id(), 10).to_bytes());
+        bytes.extend_from_slice(&7u32.to_le_bytes());
+        bytes.extend_from_slice(&[5; 32]);
+        bytes.extend_from_slice(&2u64.to_le_bytes());
+        bytes.push(0);
+        bytes.extend_from_slice(&[6; 32]);
+        bytes.push(1);
+        bytes.extend_from_slice(&[7; 32]);
+        bytes.extend_from_slice(&1u64.to_le_bytes());
+        bytes.extend(note(native_asset_id(), 3).to_bytes());
+        bytes.extend_from_slice(&1u64.to_le_bytes());
+        if version >= 2 {
+            bytes.extend_from_slice(&[8; 32]);
+        }
+        bytes.push(4);
+        bytes.extend_from_slice(b"coin");
+        bytes.push(0);
+        bytes.extend_from_slice(&5u64.to_le_bytes());
+        if version >= 2 {
+            bytes.push(1);
+            bytes.extend_from_slice(&[9; 32]);
+        }
+        bytes.extend_from_slice(&1u64.to_le_bytes());
+        bytes.extend_from_slice(&[10; 32]);
+        bytes.extend_from_slice(&1u64.to_le_bytes());
+        bytes.extend_from_slice(&100u32.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn raw_transaction_should_be_read() {
+        assert_eq!(note([0; 32], 1).to_bytes().len(), NOTE_SIZE);
+        let raw = RawTransaction::read(&raw_transaction(2)).unwrap();
+        assert_eq!(raw.version, 2);
+        assert_eq!(raw.fee, 2);
+        assert_eq!(raw.spends.len(), 1);
+        assert_eq!(raw.spends[0].note, note(native_asset_id(), 10));
+        assert_eq!(raw.spends[0].tree_size, 7);
+        assert_eq!(raw.spends[0].root_hash, [5; 32]);
+        assert_eq!(
+            raw.spends[0].auth_path,
+            vec![(false, [6; 32]), (true, [7; 32])]
+        );
+        assert_eq!(raw.outputs, vec![note(native_asset_id(), 3)]);
+        assert_eq!(
+            raw.mints,
+            vec![RawMint {
+                creator: Some([8; 32]),
+                name: "coin".to_string(),
+                metadata: "".to_string(),
+                value: 5,
+                transfer_ownership_to: Some([9; 32]),
+            }]
+        );
+        assert_eq!(
+            raw.burns,
+            vec![RawBurn {
+                asset_id: [10; 32],
+                value: 1
+            }]
+        );
+        assert_eq!(raw.expiration, 100);
+
+        let raw = RawTransaction::read(&raw_transaction(1)).unwrap();
+        assert_eq!(raw.mints[0].creator, None);
+        assert_eq!(raw.mints[0].transfer_ownership_to, None);
+        assert_eq!(raw.expiration, 100);
+    }
+
+    #[test]
+    fn malformed_raw_transaction_should_fail() {
+        let bytes = raw_transaction(2);
+        for bytes in [
+            &bytes[..bytes.len() - 1],
+            &[bytes.clone(), vec![0]].concat()[..],
+            &raw_transaction(3)[..],
+            &[],
+        ] {
+            assert!(matches!(
+                RawTransaction::read(bytes),
+                Err(OreoError::ParseError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn change_should_balance_the_transaction() {
+        let native = native_asset_id();
+        let spend = |note: RawNote| RawSpend {
+            note,
+            tree_size: 1,
+            root_hash: [5; 32],
+            auth_path: vec![],
+        };
+        let raw = RawTransaction {
+            version: 2,
+            fee: 2,
+            spends: vec![spend(note(native, 10)), spend(note([10; 32], 5))],
+            outputs: vec![note(native, 3)],
+            mints: vec![RawMint {
+                creator: None,
+                name: "coin".to_string(),
+                metadata: "".to_string(),
+                value: 4,
+                transfer_ownership_to: None,
+            }],
+            burns: vec![RawBurn {
+                asset_id: [10; 32],
+                value: 1,
+            }],
+            expiration: 0,
+        };
+
+        let change = raw.change_notes([11; 32], &[[12; 32]]).unwrap();
+        let values: Vec<([u8; 32], u64)> = change.iter().map(|x| (x.asset_id, x.value)).collect();
+        assert_eq!(values.len(), 3);
+        assert!(values.contains(&(native, 5)));
+        assert!(values.contains(&([10; 32], 4)));
+        assert!(values.contains(&([12; 32], 4)));
+        assert!(change
+            .iter()
+            .all(|x| x.owner == [11; 32] && x.sender == [11; 32] && x.memo == [0; 32]));
+
+        // no change note when the fee takes what's left
+        let raw = RawTransaction { fee: 7, ..raw };
+        assert_eq!(raw.change_notes([11; 32], &[[12; 32]]).unwrap().len(), 2);
+        let raw = RawTransaction { fee: 8, ..raw };
+        assert_eq!(
+            raw.change_notes([11; 32], &[[12; 32]]).unwrap_err(),
+            OreoError::BalanceNotEnough
+        );
+    }
+
+    #[test]
+    fn proof_generation_key_should_match_view_key() {
+        let (expected, key, view_key) = known_key();
+        let parsed = proof_generation_key(&key, &view_key).unwrap();
+        assert_eq!(parsed.ak, expected.ak);
+        assert_eq!(parsed.nsk, expected.nsk);
+
+        // nk of another nsk
+        let other = *PROOF_GENERATION_KEY_GENERATOR * jubjub::Fr::random(StdRng::seed_from_u64(8));
+        let other_view_key = format!("{}{}", &view_key[..64], hex::encode(other.to_bytes()));
+        for (key, view_key) in [
+            (key.as_str(), other_view_key.as_str()),
+            (&key[..64], view_key.as_str()),
+            ("zz", view_key.as_str()),
+        ] {
+            assert!(matches!(
+                proof_generation_key(key, view_key),
+                Err(OreoError::InvalidProofGenerationKey(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn circuits_should_be_read_back() {
+        let (key, _, _) = known_key();
+        let mut rng = StdRng::seed_from_u64(9);
+        let account = random_address(&mut rng);
+        let mut note = |value| RawNote {
+            owner: random_address(&mut rng),
+            asset_id: native_asset_id(),
+            value,
+            randomness: jubjub::Fr::random(&mut rng).to_bytes(),
+            memo: [0; 32],
+            sender: account,
+        };
+        let raw = RawTransaction {
+            version: 2,
+            fee: 1,
+            spends: vec![RawSpend {
+                note: RawNote {
+                    owner: account,
+                    ..note(10)
+                },
+                tree_size: 2,
+                root_hash: [5; 32],
+                auth_path: vec![(true, [6; 32])],
+            }],
+            outputs: vec![note(4)],
+            mints: vec![RawMint {
+                creator: None,
+                name: "coin".to_string(),
+                metadata: "".to_string(),
+                value: 3,
+                transfer_ownership_to: None,
+            }],
+            burns: vec![],
+            expiration: 0,
+        };
+        let (request, secrets) = raw.circuits(&key, account).unwrap();
+
+        assert_eq!(request.spend_circuits.len(), 1);
+        let spend = Spend::read(&request.spend_circuits[0][..]).unwrap();
+        let value_commitment = spend.value_commitment.unwrap();
+        assert_eq!(value_commitment.value, 10);
+        assert_eq!(value_commitment.randomness, secrets.spends[0]);
+        assert_eq!(spend.proof_generation_key.unwrap().ak, key.ak);
+        assert_eq!(spend.ar, Some(secrets.public_key_randomness));
+        assert_eq!(spend.anchor, Some(merkle_hash(&[5; 32]).unwrap()));
+        assert_eq!(
+            spend.auth_path,
+            vec![Some((merkle_hash(&[6; 32]).unwrap(), true))]
+        );
+        assert_eq!(spend.payment_address.unwrap().to_bytes(), account);
+
+        // the output, then the native change and the minted asset
+        assert_eq!(request.output_circuits.len(), 3);
+        assert_eq!(secrets.outputs.len(), 3);
+        for (circuit, output) in request.output_circuits.iter().zip(secrets.outputs.iter()) {
+            let read = Output::read(&circuit[..]).unwrap();
+            let value_commitment = read.value_commitment.unwrap();
+            assert_eq!(value_commitment.value, output.note.value);
+            assert_eq!(
+                value_commitment.randomness,
+                output.value_commitment_randomness
+            );
+            assert_eq!(read.esk, Some(output.esk));
+            assert_eq!(read.asset_id, output.note.asset_id);
+            assert_eq!(read.payment_address.unwrap().to_bytes(), output.note.owner);
+            assert_eq!(read.ar, Some(secrets.public_key_randomness));
+        }
+        assert_eq!(secrets.outputs[0].note, raw.outputs[0]);
+
+        assert_eq!(request.mint_asset_circuits.len(), 1);
+        let mint = MintAsset::read(&request.mint_asset_circuits[0][..]).unwrap();
+        assert_eq!(mint.proof_generation_key.unwrap().nsk, key.nsk);
+        assert_eq!(
+            mint.public_key_randomness,
+            Some(secrets.public_key_randomness)
+        );
+    }
+}
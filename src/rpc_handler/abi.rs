@@ -3,8 +3,9 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 
 use crate::{
-    constants::{IRON_NATIVE_ASSET, MAINNET_GENESIS_HASH, MAINNET_GENESIS_SEQUENCE},
-    db_handler::address_to_name,
+    constants::{NetworkProfile, IRON_NATIVE_ASSET},
+    db_handler::{address_to_name, LocalBalance},
+    error::OreoError,
     orescriptions::{get_ores, is_ores_local, Ores},
 };
 
@@ -45,22 +46,30 @@ pub struct ExportAccountRep {
 }
 
 impl ImportAccountReq {
-    pub fn to_account(&self) -> crate::db_handler::Account {
+    pub fn to_account(
+        &self,
+        network: &NetworkProfile,
+    ) -> Result<crate::db_handler::Account, OreoError> {
         let (create_head, create_hash) = match &self.created_at {
-            Some(creat) => (Some(creat.sequence as i64), Some(creat.hash.clone())),
+            Some(creat) => {
+                network.check_created_at(creat.sequence, &creat.hash)?;
+                (Some(creat.sequence as i64), Some(creat.hash.clone()))
+            }
             None => (None, None),
         };
-        crate::db_handler::Account {
+        Ok(crate::db_handler::Account {
             address: self.public_address.clone(),
             name: address_to_name(&self.public_address),
             create_head,
             create_hash: create_hash.clone(),
-            head: create_head.unwrap_or(MAINNET_GENESIS_SEQUENCE),
-            hash: create_hash.unwrap_or(MAINNET_GENESIS_HASH.to_string()),
+            head: create_head.unwrap_or(network.genesis_sequence),
+            hash: create_hash.unwrap_or(network.genesis_hash.clone()),
             in_vk: self.incoming_view_key.clone(),
             out_vk: self.outgoing_view_key.clone(),
             vk: self.view_key.clone(),
-        }
+            network_id: network.network.id(),
+            worker_id: 0,
+        })
     }
 }
 
@@ -82,9 +91,9 @@ pub struct GetBalancesReq {
     pub confirmations: Option<u32>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct AssetStatus {
-    status: String,
+    pub status: String,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -107,6 +116,36 @@ pub struct GetBalancesRep {
 }
 
 impl GetBalancesRep {
+    /// Balances computed from stored notes, `assets` provides names and verification
+    pub fn from_local(
+        account: String,
+        balances: Vec<LocalBalance>,
+        assets: &[RpcAsset],
+        head: u64,
+    ) -> Self {
+        let balances = balances
+            .into_iter()
+            .map(|balance| {
+                let asset = assets.iter().find(|x| x.id == balance.asset_id);
+                AssetBalance {
+                    asset_name: asset.map(|x| x.name.clone()).unwrap_or_default(),
+                    asset_verification: asset.map(|x| x.verification.clone()).unwrap_or(
+                        AssetStatus {
+                            status: "unknown".to_string(),
+                        },
+                    ),
+                    confirmed: balance.confirmed.to_string(),
+                    unconfirmed: balance.unconfirmed.to_string(),
+                    pending: balance.pending.to_string(),
+                    available: balance.available.to_string(),
+                    sequence: Some(head),
+                    asset_id: balance.asset_id,
+                }
+            })
+            .collect();
+        Self { account, balances }
+    }
+
     pub fn verified_asset(base: Self) -> Self {
         Self {
             balances: base
@@ -124,13 +163,13 @@ impl GetBalancesRep {
         }
     }
 
-    pub async fn ores(base: Self) -> Vec<Ores> {
+    pub async fn ores(base: Self, endpoint: &str) -> Vec<Ores> {
         let mut result = vec![];
         for asset in base.balances.iter() {
             if !is_ores_local(asset) {
                 continue;
             }
-            if let Ok(ores) = get_ores(&asset.asset_id).await {
+            if let Ok(ores) = get_ores(endpoint, &asset.asset_id).await {
                 result.push(ores);
             }
         }
@@ -276,7 +315,7 @@ pub struct RpcAsset {
     pub id: String,
     pub name: String,
     pub metadata: String,
-    pub verification: String,
+    pub verification: AssetStatus,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -299,3 +338,49 @@ pub struct GetNoteWitnessRep {
     pub root_hash: String,
     pub auth_path: Vec<AuthPath>,
 }
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GetBlockReq {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub serialized: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcBlockHeader {
+    pub hash: String,
+    pub sequence: u64,
+    pub previous_block_hash: String,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GetBlockRep {
+    pub block: RpcBlockHeader,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RpcBlockTransaction {
+    pub hash: String,
+    pub serialized: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcBlock {
+    pub hash: String,
+    pub sequence: u64,
+    pub previous_block_hash: String,
+    pub timestamp: u64,
+    pub note_size: Option<u64>,
+    pub transactions: Vec<RpcBlockTransaction>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GetFullBlockRep {
+    pub block: RpcBlock,
+}
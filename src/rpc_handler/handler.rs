@@ -1,9 +1,9 @@
-use std::{fmt::Debug, time::Duration};
+use std::{fmt::Debug, path::PathBuf, str::FromStr, time::Duration};
 
-use serde::Deserialize;
-use serde_json::json;
+use reqwest::{Client, Error, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use tracing::debug;
-use ureq::{Agent, AgentBuilder, Error, Response};
 
 use crate::{
     error::OreoError,
@@ -12,22 +12,117 @@ use crate::{
     },
 };
 
-use super::{abi::*, RpcError};
+use super::{abi::*, RpcError, SocketAddress, SocketTransport};
+
+/// How the server talks to an Ironfish node, `endpoint` is `host:port` for http and tcp and a
+/// socket path for ipc
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RpcTransportKind {
+    #[default]
+    Http,
+    Tcp,
+    Ipc,
+}
+
+impl FromStr for RpcTransportKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "http" => Ok(RpcTransportKind::Http),
+            "tcp" => Ok(RpcTransportKind::Tcp),
+            "ipc" => Ok(RpcTransportKind::Ipc),
+            _ => Err(format!("Unknown transport `{}`", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum RpcTransport {
+    Http(Client),
+    Socket(SocketTransport),
+}
 
 #[derive(Debug, Clone)]
 pub struct RpcHandler {
     pub endpoint: String,
-    pub agent: Agent,
+    pub transport: RpcTransport,
+    pub timeout: Duration,
 }
 
 impl RpcHandler {
-    pub fn new(endpoint: String) -> Self {
+    pub fn new(endpoint: String, timeout: Duration) -> Self {
+        Self::with_transport(endpoint, RpcTransportKind::Http, timeout)
+    }
+
+    pub fn with_transport(endpoint: String, kind: RpcTransportKind, timeout: Duration) -> Self {
+        let transport = match kind {
+            RpcTransportKind::Http => RpcTransport::Http(
+                Client::builder()
+                    .connect_timeout(timeout)
+                    .pool_idle_timeout(Duration::from_secs(90))
+                    .pool_max_idle_per_host(32)
+                    .tcp_keepalive(Duration::from_secs(60))
+                    .build()
+                    .expect("Failed to build rpc client"),
+            ),
+            RpcTransportKind::Tcp => {
+                RpcTransport::Socket(SocketTransport::new(SocketAddress::Tcp(endpoint.clone())))
+            }
+            RpcTransportKind::Ipc => RpcTransport::Socket(SocketTransport::new(
+                SocketAddress::Ipc(PathBuf::from(&endpoint)),
+            )),
+        };
         Self {
             endpoint,
-            agent: AgentBuilder::new()
-                .timeout_read(Duration::from_secs(5))
-                .timeout_write(Duration::from_secs(5))
-                .build(),
+            transport,
+            timeout,
+        }
+    }
+
+    fn url(&self, route: &str) -> String {
+        format!("http://{}/{}", self.endpoint, route)
+    }
+
+    /// Post `body` to the node with the default timeout of this handler
+    pub async fn post<Req, Rep>(
+        &self,
+        route: &str,
+        body: &Req,
+    ) -> Result<RpcResponse<Rep>, OreoError>
+    where
+        Req: Serialize + ?Sized,
+        Rep: Debug + for<'a> Deserialize<'a>,
+    {
+        self.post_with_timeout(route, body, self.timeout).await
+    }
+
+    /// Post `body` to the node, the request is aborted after `timeout` or once the returned
+    /// future is dropped
+    pub async fn post_with_timeout<Req, Rep>(
+        &self,
+        route: &str,
+        body: &Req,
+        timeout: Duration,
+    ) -> Result<RpcResponse<Rep>, OreoError>
+    where
+        Req: Serialize + ?Sized,
+        Rep: Debug + for<'a> Deserialize<'a>,
+    {
+        match &self.transport {
+            RpcTransport::Http(client) => {
+                let resp = client
+                    .post(self.url(route))
+                    .timeout(timeout)
+                    .json(body)
+                    .send()
+                    .await;
+                handle_response(resp).await
+            }
+            RpcTransport::Socket(socket) => {
+                into_oreo_result(socket.request(route, body, timeout).await)
+            }
         }
     }
 
@@ -35,13 +130,8 @@ impl RpcHandler {
         &self,
         req: ImportAccountReq,
     ) -> Result<RpcResponse<ImportAccountRep>, OreoError> {
-        let path = format!("http://{}/wallet/importAccount", self.endpoint);
-        let resp = self
-            .agent
-            .clone()
-            .post(&path)
-            .send_json(ureq::json!({"account": req}));
-        handle_response(resp)
+        self.post("wallet/importAccount", &json!({"account": req}))
+            .await
     }
 
     pub async fn remove_account(
@@ -49,124 +139,150 @@ impl RpcHandler {
         req: RemoveAccountReq,
     ) -> Result<RpcResponse<RemoveAccountRep>, OreoError> {
         debug!("req: {:?}", req);
-        let path = format!("http://{}/wallet/removeAccount", self.endpoint);
-        let resp = self
-            .agent
-            .clone()
-            .post(&path)
-            .send_json(&req)
-            .map(|res| match res.status() {
-                200 => Response::new(200, "OK", "{\"status\":200,\"data\":{\"removed\":true}}")
-                    .unwrap(),
-                _ => res,
-            });
-        handle_response(resp)
+        let removed = RpcResponse {
+            status: 200,
+            data: RemoveAccountRep { removed: true },
+        };
+        match &self.transport {
+            RpcTransport::Http(client) => {
+                let resp = client
+                    .post(self.url("wallet/removeAccount"))
+                    .timeout(self.timeout)
+                    .json(&req)
+                    .send()
+                    .await;
+                match resp {
+                    Ok(res) if res.status().as_u16() == 200 => Ok(removed),
+                    _ => handle_response(resp).await,
+                }
+            }
+            RpcTransport::Socket(socket) => {
+                let resp = socket
+                    .request::<_, Value>("wallet/removeAccount", &req, self.timeout)
+                    .await;
+                into_oreo_result(resp.map(|_| removed))
+            }
+        }
     }
 
     pub async fn get_balance(
         &self,
         req: GetBalancesReq,
     ) -> Result<RpcResponse<GetBalancesRep>, OreoError> {
-        let path = format!("http://{}/wallet/getBalances", self.endpoint);
-        let resp = self.agent.clone().post(&path).send_json(&req);
-        handle_response(resp)
+        self.post("wallet/getBalances", &req).await
     }
 
     pub async fn get_transactions(
         &self,
         req: GetTransactionsReq,
     ) -> Result<RpcResponse<GetTransactionsRep>, OreoError> {
-        let path = format!("http://{}/wallet/getAccountTransactions", self.endpoint);
-        let resp = self.agent.clone().post(&path).send_json(&req);
-        handle_response(resp)
+        self.post("wallet/getAccountTransactions", &req).await
     }
 
     pub async fn create_transaction(
         &self,
         req: CreateTxReq,
     ) -> Result<RpcResponse<CreateTxRep>, OreoError> {
-        let path = format!("http://{}/wallet/createTransaction", self.endpoint);
-        let resp = self.agent.clone().post(&path).send_json(&req);
-        handle_response(resp)
+        self.post("wallet/createTransaction", &req).await
     }
 
     pub async fn broadcast_transaction(
         &self,
         req: BroadcastTxReq,
     ) -> Result<RpcResponse<BroadcastTxRep>, OreoError> {
-        let path = format!("http://{}/chain/broadcastTransaction", self.endpoint);
-        let resp = self.agent.clone().post(&path).send_json(&req);
-        handle_response(resp)
+        self.post("chain/broadcastTransaction", &req).await
     }
 
     pub async fn get_account_status(
         &self,
         req: GetAccountStatusReq,
     ) -> Result<RpcResponse<GetAccountStatusRep>, OreoError> {
-        let path = format!("http://{}/wallet/getAccountStatus", self.endpoint);
-        let resp = self.agent.clone().post(&path).send_json(&req);
-        handle_response(resp)
+        self.post("wallet/getAccountStatus", &req).await
     }
 
     pub async fn get_latest_block(&self) -> Result<RpcResponse<GetLatestBlockRep>, OreoError> {
-        let path = format!("http://{}/chain/getChainInfo", self.endpoint);
-        let resp = self.agent.clone().get(&path).call();
-        handle_response(resp)
+        match &self.transport {
+            RpcTransport::Http(client) => {
+                let resp = client
+                    .get(self.url("chain/getChainInfo"))
+                    .timeout(self.timeout)
+                    .send()
+                    .await;
+                handle_response(resp).await
+            }
+            RpcTransport::Socket(socket) => into_oreo_result(
+                socket
+                    .request("chain/getChainInfo", &json!({}), self.timeout)
+                    .await,
+            ),
+        }
     }
 
     pub async fn get_account_transaction(
         &self,
         req: GetAccountTransactionReq,
     ) -> Result<RpcResponse<GetAccountTransactionRep>, OreoError> {
-        let path = format!("http://{}/wallet/getAccountTransaction", self.endpoint);
-        let resp = self.agent.clone().post(&path).send_json(&req);
-        handle_response(resp)
+        self.post("wallet/getAccountTransaction", &req).await
     }
 
     pub async fn get_asset(&self, id: String) -> Result<RpcResponse<RpcAsset>, OreoError> {
-        let path = format!("http://{}/chain/getAsset", self.endpoint);
-        let resp = self.agent.clone().post(&path).send_json(&json!({
-            "id": id
-        }));
-        handle_response(resp)
+        self.post("chain/getAsset", &json!({ "id": id })).await
     }
 
     pub async fn get_note_witness(
         &self,
         req: GetNoteWitnessReq,
     ) -> Result<RpcResponse<GetNoteWitnessRep>, OreoError> {
-        let path = format!("http://{}/chain/getNoteWitness", self.endpoint);
-        let resp = self.agent.clone().post(&path).send_json(req);
-        handle_response(resp)
+        self.post("chain/getNoteWitness", &req).await
+    }
+
+    pub async fn get_block(&self, req: GetBlockReq) -> Result<RpcResponse<GetBlockRep>, OreoError> {
+        self.post("chain/getBlock", &req).await
+    }
+
+    /// Block at `sequence` with serialized transactions
+    pub async fn get_full_block(
+        &self,
+        sequence: u64,
+    ) -> Result<RpcResponse<GetFullBlockRep>, OreoError> {
+        self.post(
+            "chain/getBlock",
+            &GetBlockReq {
+                hash: None,
+                sequence: Some(sequence),
+                serialized: Some(true),
+            },
+        )
+        .await
     }
 
     pub async fn export_account(
         &self,
         account: String,
     ) -> Result<RpcResponse<ExportAccountRep>, OreoError> {
-        let path = format!("http://{}/wallet/exportAccount", self.endpoint);
-        let resp = self
-            .agent
-            .clone()
-            .post(&path)
-            .send_json(json!({"account": account, "format": "JSON".to_string()}));
-        handle_response(resp)
+        self.post(
+            "wallet/exportAccount",
+            &json!({"account": account, "format": "JSON".to_string()}),
+        )
+        .await
     }
 }
 
-pub fn handle_response<S: Debug + for<'a> Deserialize<'a>>(
+pub async fn handle_response<S: Debug + for<'a> Deserialize<'a>>(
     resp: Result<Response, Error>,
 ) -> Result<RpcResponse<S>, OreoError> {
     let res = match resp {
-        Ok(response) => match response.into_json::<RpcResponse<S>>() {
-            Ok(data) => Ok(data),
-            Err(e) => Err(RpcError {
-                code: "Unknown".into(),
-                status: 606,
-                message: e.to_string(),
-            }),
-        },
-        Err(ureq::Error::Status(_code, response)) => match response.into_json::<RpcError>() {
+        Ok(response) if response.status().is_success() => {
+            match response.json::<RpcResponse<S>>().await {
+                Ok(data) => Ok(data),
+                Err(e) => Err(RpcError {
+                    code: "Unknown".into(),
+                    status: 606,
+                    message: e.to_string(),
+                }),
+            }
+        }
+        Ok(response) => match response.json::<RpcError>().await {
             Ok(data) => Err(data),
             Err(e) => Err(RpcError {
                 code: "Unknown".into(),
@@ -180,6 +296,12 @@ pub fn handle_response<S: Debug + for<'a> Deserialize<'a>>(
             message: e.to_string(),
         }),
     };
+    into_oreo_result(res)
+}
+
+fn into_oreo_result<S: Debug>(
+    res: Result<RpcResponse<S>, RpcError>,
+) -> Result<RpcResponse<S>, OreoError> {
     debug!("Handle rpc response: {:?}", res);
     match res {
         Ok(data) => Ok(data),
@@ -1,13 +1,17 @@
 pub mod abi;
 mod handler;
+mod pool;
+mod socket;
 
 pub use handler::*;
+pub use pool::*;
+pub use socket::*;
 
 use serde::{Deserialize, Serialize};
 
 use crate::error::OreoError;
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub struct RpcError {
     pub code: String,
     pub status: u16,
@@ -17,14 +21,113 @@ pub struct RpcError {
 impl TryFrom<RpcError> for OreoError {
     type Error = OreoError;
 
+    /// Map the error codes of the Ironfish rpc, validation errors are told apart by their
+    /// message when the node has no dedicated code for them, generic errors only when the
+    /// message is a known one
     fn try_from(value: RpcError) -> Result<Self, Self::Error> {
-        match &value.code as &str {
+        let message = value.message.to_lowercase();
+        match value.code.as_str() {
             "insufficient-balance" => Ok(OreoError::BalanceNotEnough),
-            "account-exists" => {
-                // Should never happen
-                return Ok(OreoError::Duplicate("0x00".to_string()));
+            "account-exists" | "duplicate-account-name" => {
+                Ok(OreoError::NodeAccountExists(value.message))
             }
-            _ => Ok(OreoError::InternalRpcError),
+            "not-found" => Ok(OreoError::NodeNotFound(value.message)),
+            "unauthenticated" => Ok(OreoError::NodeUnauthenticated),
+            "route-not-found" => Ok(OreoError::NodeRouteNotFound(value.message)),
+            "validation" if message.contains("synced") => Ok(OreoError::Syncing),
+            "validation" if message.contains("expir") => {
+                Ok(OreoError::TransactionExpired(value.message))
+            }
+            "validation" if message.contains("fee") => Ok(OreoError::InvalidFee(value.message)),
+            "validation" | "import-account-name-required" => {
+                Ok(OreoError::NodeValidation(value.message))
+            }
+            "error" if message.starts_with(NOT_SYNCED_MESSAGE) => Ok(OreoError::Syncing),
+            "error" if message.starts_with(INVALID_EXPIRATION_MESSAGE) => {
+                Ok(OreoError::TransactionExpired(value.message))
+            }
+            _ => Ok(OreoError::InternalRpcError(value)),
+        }
+    }
+}
+
+/// Known messages of generic node errors, lowercase, the node may append details
+const NOT_SYNCED_MESSAGE: &str = "your node must be synced with the iron fish network";
+const INVALID_EXPIRATION_MESSAGE: &str = "invalid expiration sequence for transaction";
+
+#[cfg(test)]
+mod tests {
+    use crate::error::OreoError;
+
+    use super::RpcError;
+
+    fn rpc_error(code: &str, message: &str) -> OreoError {
+        OreoError::try_from(RpcError {
+            code: code.to_string(),
+            status: 400,
+            message: message.to_string(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn node_errors_should_be_translated() {
+        assert_eq!(
+            rpc_error("insufficient-balance", "Not enough"),
+            OreoError::BalanceNotEnough
+        );
+        assert_eq!(
+            rpc_error("account-exists", "Account already exists"),
+            OreoError::NodeAccountExists("Account already exists".to_string())
+        );
+        assert_eq!(
+            rpc_error("not-found", "No transaction found"),
+            OreoError::NodeNotFound("No transaction found".to_string())
+        );
+        assert_eq!(
+            rpc_error("unauthenticated", ""),
+            OreoError::NodeUnauthenticated
+        );
+        assert_eq!(
+            rpc_error(
+                "validation",
+                "Your node must be synced with the Iron Fish network to send a transaction"
+            ),
+            OreoError::Syncing
+        );
+        assert_eq!(
+            rpc_error("error", "Invalid expiration sequence for transaction"),
+            OreoError::TransactionExpired(
+                "Invalid expiration sequence for transaction".to_string()
+            )
+        );
+        assert_eq!(
+            rpc_error("validation", "Fee must be at least 1"),
+            OreoError::InvalidFee("Fee must be at least 1".to_string())
+        );
+        assert_eq!(
+            rpc_error("validation", "Invalid public address"),
+            OreoError::NodeValidation("Invalid public address".to_string())
+        );
+        assert_eq!(rpc_error("error", "Oops").code(), 605);
+    }
+
+    #[test]
+    fn generic_node_errors_should_not_be_guessed() {
+        assert_eq!(
+            rpc_error(
+                "error",
+                "Your node must be synced with the Iron Fish network to send a transaction"
+            ),
+            OreoError::Syncing
+        );
+        for message in [
+            "failed to estimate fee: account not found",
+            "Account not found while computing expiration",
+            "Wallet is not synced with the chain",
+            "Could not decrypt note, invalid expiration sequence for transaction",
+        ] {
+            assert_eq!(rpc_error("error", message).code(), 605, "{}", message);
         }
     }
 }
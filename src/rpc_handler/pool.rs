@@ -0,0 +1,255 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
+use futures::future::join_all;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::{error::OreoError, web_handlers::abi::GetAccountStatusReq};
+
+use super::{RpcHandler, RpcTransportKind};
+
+#[derive(Debug)]
+pub struct RpcNode {
+    pub handler: RpcHandler,
+    healthy: AtomicBool,
+    head: AtomicU64,
+}
+
+impl RpcNode {
+    pub fn new(handler: RpcHandler) -> Self {
+        Self {
+            handler,
+            // optimistic until the first health check finishes
+            healthy: AtomicBool::new(true),
+            head: AtomicU64::new(0),
+        }
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    pub fn head(&self) -> u64 {
+        self.head.load(Ordering::Relaxed)
+    }
+}
+
+/// A set of Ironfish nodes, stateless chain calls are balanced across healthy nodes while
+/// wallet calls of an account stay on the node which imported its view key.
+#[derive(Debug, Clone)]
+pub struct RpcPool {
+    pub nodes: Arc<Vec<RpcNode>>,
+    next: Arc<AtomicUsize>,
+    pins: Arc<RwLock<HashMap<String, usize>>>,
+    max_lag: u64,
+}
+
+impl RpcPool {
+    pub fn new(
+        endpoints: Vec<String>,
+        transport: RpcTransportKind,
+        timeout: Duration,
+        max_lag: u64,
+    ) -> Self {
+        let nodes = endpoints
+            .into_iter()
+            .map(|endpoint| RpcNode::new(RpcHandler::with_transport(endpoint, transport, timeout)))
+            .collect();
+        Self {
+            nodes: Arc::new(nodes),
+            next: Arc::new(AtomicUsize::new(0)),
+            pins: Arc::new(RwLock::new(HashMap::new())),
+            max_lag,
+        }
+    }
+
+    /// Pick the next healthy node in round robin order
+    pub fn any(&self) -> Result<(usize, RpcHandler), OreoError> {
+        let len = self.nodes.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        (0..len)
+            .map(|offset| (start + offset) % len)
+            .find(|idx| self.nodes[*idx].is_healthy())
+            .map(|idx| (idx, self.nodes[idx].handler.clone()))
+            .ok_or(OreoError::Syncing)
+    }
+
+    /// Node holding the wallet of `account`, discovered by asking every healthy node if not
+    /// pinned yet. Only nodes answering that they don't know the account are ruled out, so
+    /// an unreachable or unhealthy node fails the lookup rather than reporting `NoImported`
+    pub async fn for_account(&self, account: &str) -> Result<RpcHandler, OreoError> {
+        if let Some(idx) = self.pinned(account) {
+            let node = &self.nodes[idx];
+            if !node.is_healthy() {
+                warn!(
+                    "Account {} is pinned to unhealthy node {}",
+                    account, node.handler.endpoint
+                );
+            }
+            return Ok(node.handler.clone());
+        }
+        let mut failure = None;
+        for (idx, node) in self.nodes.iter().enumerate() {
+            if !node.is_healthy() {
+                failure.get_or_insert(OreoError::Syncing);
+                continue;
+            }
+            let status = node
+                .handler
+                .get_account_status(GetAccountStatusReq {
+                    account: account.to_string(),
+                })
+                .await;
+            match status {
+                Ok(_) => {
+                    debug!(
+                        "Account {} found on node {}",
+                        account, node.handler.endpoint
+                    );
+                    self.pin(account, idx);
+                    return Ok(node.handler.clone());
+                }
+                Err(OreoError::NodeNotFound(_)) | Err(OreoError::NodeValidation(_)) => {}
+                Err(e) => {
+                    warn!(
+                        "Failed to look account {} up on node {}: {}",
+                        account, node.handler.endpoint, e
+                    );
+                    failure = Some(e);
+                }
+            }
+        }
+        Err(failure.unwrap_or(OreoError::NoImported(account.to_string())))
+    }
+
+    pub fn pinned(&self, account: &str) -> Option<usize> {
+        self.pins.read().unwrap().get(account).copied()
+    }
+
+    pub fn pin(&self, account: &str, idx: usize) {
+        self.pins.write().unwrap().insert(account.to_string(), idx);
+    }
+
+    pub fn unpin(&self, account: &str) {
+        self.pins.write().unwrap().remove(account);
+    }
+
+    /// Query `chain/getChainInfo` of every node and refresh their health
+    pub async fn check_health(&self) {
+        let heads = join_all(self.nodes.iter().map(|node| async {
+            node.handler
+                .get_latest_block()
+                .await
+                .ok()
+                .and_then(|res| res.data.current_block_identifier.index.parse::<u64>().ok())
+        }))
+        .await;
+        self.update_health(heads);
+    }
+
+    fn update_health(&self, heads: Vec<Option<u64>>) {
+        let best = heads.iter().flatten().max().copied().unwrap_or(0);
+        for (node, head) in self.nodes.iter().zip(heads.into_iter()) {
+            let healthy = match head {
+                Some(head) => {
+                    node.head.store(head, Ordering::Relaxed);
+                    best - head <= self.max_lag
+                }
+                None => false,
+            };
+            if node.is_healthy() != healthy {
+                info!(
+                    "Node {} is {} now, head {}, best {}",
+                    node.handler.endpoint,
+                    if healthy { "healthy" } else { "unhealthy" },
+                    node.head(),
+                    best
+                );
+            }
+            node.healthy.store(healthy, Ordering::Relaxed);
+        }
+    }
+
+    pub fn spawn_health_check(&self, interval: Duration) -> JoinHandle<()> {
+        let pool = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                pool.check_health().await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{error::OreoError, rpc_handler::RpcTransportKind};
+
+    use super::RpcPool;
+
+    fn get_pool() -> RpcPool {
+        RpcPool::new(
+            vec![
+                "127.0.0.1:9092".to_string(),
+                "127.0.0.1:9093".to_string(),
+                "127.0.0.1:9094".to_string(),
+            ],
+            RpcTransportKind::Http,
+            Duration::from_secs(5),
+            5,
+        )
+    }
+
+    #[test]
+    fn any_should_round_robin_healthy_nodes() {
+        let pool = get_pool();
+        pool.update_health(vec![Some(100), Some(90), Some(98)]);
+        let picked: Vec<usize> = (0..4).map(|_| pool.any().unwrap().0).collect();
+        assert_eq!(picked, vec![0, 2, 2, 0]);
+        assert!(!pool.nodes[1].is_healthy());
+        assert_eq!(pool.nodes[1].head(), 90);
+    }
+
+    #[test]
+    fn any_should_fail_without_healthy_node() {
+        let pool = get_pool();
+        pool.update_health(vec![None, None, None]);
+        assert_eq!(pool.any().unwrap_err(), OreoError::Syncing);
+    }
+
+    #[tokio::test]
+    async fn for_account_should_not_hide_outages() {
+        let pool = get_pool();
+        pool.update_health(vec![None, None, None]);
+        assert_eq!(
+            pool.for_account("d63ba13d7c").await.unwrap_err(),
+            OreoError::Syncing
+        );
+
+        // nothing listens on the pool endpoints
+        pool.update_health(vec![Some(100), None, None]);
+        let error = pool.for_account("d63ba13d7c").await.unwrap_err();
+        assert_eq!(error.code(), 605);
+        assert_eq!(pool.pinned("d63ba13d7c"), None);
+    }
+
+    #[tokio::test]
+    async fn for_account_should_use_pinned_node() {
+        let pool = get_pool();
+        pool.pin("d63ba13d7c", 2);
+        let handler = pool.for_account("d63ba13d7c").await.unwrap();
+        assert_eq!(handler.endpoint, "127.0.0.1:9094");
+        pool.unpin("d63ba13d7c");
+        assert_eq!(pool.pinned("d63ba13d7c"), None);
+    }
+}
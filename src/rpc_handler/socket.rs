@@ -0,0 +1,270 @@
+use std::{
+    fmt::Debug,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
+
+use super::{abi::RpcResponse, RpcError};
+
+/// Messages on the node's socket adapter are json documents separated by this byte
+pub const SOCKET_MESSAGE_DELIMITER: u8 = b'\n';
+
+#[derive(Debug, Clone)]
+pub enum SocketAddress {
+    Tcp(String),
+    Ipc(PathBuf),
+}
+
+#[derive(Debug, Serialize)]
+struct SocketRequest<'a, T: ?Sized> {
+    r#type: &'static str,
+    data: SocketRequestData<'a, T>,
+}
+
+#[derive(Debug, Serialize)]
+struct SocketRequestData<'a, T: ?Sized> {
+    mid: u64,
+    r#type: &'a str,
+    data: &'a T,
+}
+
+#[derive(Debug, Deserialize)]
+struct SocketMessage {
+    r#type: String,
+    data: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct SocketResponse {
+    id: u64,
+    status: u16,
+    data: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SocketError {
+    code: Option<String>,
+    message: Option<String>,
+}
+
+/// Ironfish rpc over the node's tcp or ipc socket adapter, one connection per call
+#[derive(Debug, Clone)]
+pub struct SocketTransport {
+    pub address: SocketAddress,
+    next_id: Arc<AtomicU64>,
+}
+
+impl SocketTransport {
+    pub fn new(address: SocketAddress) -> Self {
+        Self {
+            address,
+            next_id: Arc::new(AtomicU64::new(1)),
+        }
+    }
+
+    pub async fn request<Req, Rep>(
+        &self,
+        route: &str,
+        body: &Req,
+        timeout: Duration,
+    ) -> Result<RpcResponse<Rep>, RpcError>
+    where
+        Req: Serialize + ?Sized,
+        Rep: Debug + for<'a> Deserialize<'a>,
+    {
+        let (status, data) = self.request_raw(route, body, timeout).await?;
+        if !(200..300).contains(&status) {
+            let error = serde_json::from_value::<SocketError>(data).ok();
+            return Err(RpcError {
+                code: error
+                    .as_ref()
+                    .and_then(|e| e.code.clone())
+                    .unwrap_or("Unknown".into()),
+                status,
+                message: error.and_then(|e| e.message).unwrap_or_default(),
+            });
+        }
+        serde_json::from_value::<Rep>(data)
+            .map(|data| RpcResponse { status, data })
+            .map_err(|e| unknown_error(e.to_string()))
+    }
+
+    /// Send one request and return status and data of the matching response
+    pub async fn request_raw<Req: Serialize + ?Sized>(
+        &self,
+        route: &str,
+        body: &Req,
+        timeout: Duration,
+    ) -> Result<(u16, Value), RpcError> {
+        let mid = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut payload = serde_json::to_vec(&SocketRequest {
+            r#type: "message",
+            data: SocketRequestData {
+                mid,
+                r#type: route,
+                data: body,
+            },
+        })
+        .map_err(|e| unknown_error(e.to_string()))?;
+        payload.push(SOCKET_MESSAGE_DELIMITER);
+        let exchange = async {
+            match &self.address {
+                SocketAddress::Tcp(addr) => {
+                    let stream = TcpStream::connect(addr)
+                        .await
+                        .map_err(|e| unknown_error(e.to_string()))?;
+                    roundtrip(stream, &payload, mid).await
+                }
+                #[cfg(unix)]
+                SocketAddress::Ipc(path) => {
+                    let stream = tokio::net::UnixStream::connect(path)
+                        .await
+                        .map_err(|e| unknown_error(e.to_string()))?;
+                    roundtrip(stream, &payload, mid).await
+                }
+                #[cfg(not(unix))]
+                SocketAddress::Ipc(_) => Err(unknown_error(
+                    "ipc transport is supported on unix only".into(),
+                )),
+            }
+        };
+        tokio::time::timeout(timeout, exchange)
+            .await
+            .map_err(|_| unknown_error(format!("rpc {} timed out", route)))?
+    }
+}
+
+async fn roundtrip<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: S,
+    payload: &[u8],
+    mid: u64,
+) -> Result<(u16, Value), RpcError> {
+    let (reader, mut writer) = tokio::io::split(stream);
+    writer
+        .write_all(payload)
+        .await
+        .map_err(|e| unknown_error(e.to_string()))?;
+    let mut reader = BufReader::new(reader);
+    loop {
+        let mut buf = vec![];
+        let read = reader
+            .read_until(SOCKET_MESSAGE_DELIMITER, &mut buf)
+            .await
+            .map_err(|e| unknown_error(e.to_string()))?;
+        if read == 0 {
+            return Err(unknown_error("rpc socket closed".into()));
+        }
+        if buf.last() == Some(&SOCKET_MESSAGE_DELIMITER) {
+            buf.pop();
+        }
+        if buf.iter().all(|x| x.is_ascii_whitespace()) {
+            continue;
+        }
+        let message: SocketMessage =
+            serde_json::from_slice(&buf).map_err(|e| unknown_error(e.to_string()))?;
+        match message.r#type.as_str() {
+            "message" => {
+                let response: SocketResponse = serde_json::from_value(message.data)
+                    .map_err(|e| unknown_error(e.to_string()))?;
+                if response.id == mid {
+                    return Ok((response.status, response.data.unwrap_or(Value::Null)));
+                }
+            }
+            "malformedRequest" => {
+                return Err(RpcError {
+                    code: "malformed-request".into(),
+                    status: 400,
+                    message: message.data.to_string(),
+                })
+            }
+            // stream events of long running routes, only the final message matters
+            _ => continue,
+        }
+    }
+}
+
+fn unknown_error(message: String) -> RpcError {
+    RpcError {
+        code: "Unknown".into(),
+        status: 606,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::{json, Value};
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+
+    use super::{SocketAddress, SocketTransport};
+
+    async fn mock_node(status: u16, data: Value) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut line = String::new();
+            BufReader::new(reader).read_line(&mut line).await.unwrap();
+            let request: Value = serde_json::from_str(&line).unwrap();
+            let mid = request["data"]["mid"].as_u64().unwrap();
+            assert_eq!(request["data"]["type"], "chain/getAsset");
+            let stream = json!({"type": "stream", "data": {"id": mid, "data": {}}});
+            let response =
+                json!({"type": "message", "data": {"id": mid, "status": status, "data": data}});
+            writer
+                .write_all(format!("{}\n{}\n", stream, response).as_bytes())
+                .await
+                .unwrap();
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn socket_request_should_work() {
+        let addr = mock_node(200, json!({"id": "0x01"})).await;
+        let transport = SocketTransport::new(SocketAddress::Tcp(addr));
+        let response = transport
+            .request::<_, Value>(
+                "chain/getAsset",
+                &json!({"id": "0x01"}),
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.data["id"], "0x01");
+    }
+
+    #[tokio::test]
+    async fn socket_error_should_be_mapped() {
+        let addr = mock_node(
+            400,
+            json!({"code": "insufficient-balance", "message": "Not enough"}),
+        )
+        .await;
+        let transport = SocketTransport::new(SocketAddress::Tcp(addr));
+        let error = transport
+            .request::<_, Value>("chain/getAsset", &json!({}), Duration::from_secs(5))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, "insufficient-balance");
+        assert_eq!(error.status, 400);
+    }
+}
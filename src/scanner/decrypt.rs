@@ -0,0 +1,134 @@
+use ironfish_rust::{IncomingViewKey, Note, OutgoingViewKey, Transaction, ViewKey};
+use tracing::warn;
+
+use crate::{
+    db_handler::{Account, DecryptedNote, SpentNullifier},
+    error::OreoError,
+    rpc_handler::abi::RpcBlock,
+};
+
+/// Parsed view keys of an imported account
+#[derive(Clone)]
+pub struct AccountKeys {
+    pub address: String,
+    pub incoming: IncomingViewKey,
+    pub outgoing: OutgoingViewKey,
+    pub view: ViewKey,
+}
+
+impl AccountKeys {
+    pub fn from_account(account: &Account) -> Result<Self, OreoError> {
+        let parse_error = || OreoError::ParseError(account.address.clone());
+        Ok(Self {
+            address: account.address.clone(),
+            incoming: IncomingViewKey::from_hex(&account.in_vk).map_err(|_| parse_error())?,
+            outgoing: OutgoingViewKey::from_hex(&account.out_vk).map_err(|_| parse_error())?,
+            view: ViewKey::from_hex(&account.vk).map_err(|_| parse_error())?,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ScannedBlock {
+    pub notes: Vec<DecryptedNote>,
+    pub spends: Vec<SpentNullifier>,
+}
+
+/// Trial decrypt every output of `block` with `keys` and collect all revealed nullifiers
+pub fn decrypt_block(block: &RpcBlock, keys: &[AccountKeys]) -> Result<ScannedBlock, OreoError> {
+    let mut transactions = Vec::with_capacity(block.transactions.len());
+    for tx in block.transactions.iter() {
+        let serialized = tx
+            .serialized
+            .as_ref()
+            .ok_or(OreoError::ParseError(tx.hash.clone()))?;
+        let bytes = hex::decode(serialized).map_err(|_| OreoError::ParseError(tx.hash.clone()))?;
+        let transaction =
+            Transaction::read(&bytes[..]).map_err(|_| OreoError::ParseError(tx.hash.clone()))?;
+        transactions.push((tx.hash.clone(), transaction));
+    }
+    let outputs: u64 = transactions
+        .iter()
+        .map(|(_, tx)| tx.outputs().len() as u64)
+        .sum();
+    // note_size is the size of the note tree after this block
+    let note_size = block
+        .note_size
+        .ok_or(OreoError::ParseError(block.hash.clone()))?;
+    let mut position = note_size
+        .checked_sub(outputs)
+        .ok_or(OreoError::ParseError(block.hash.clone()))?;
+
+    let sequence = block.sequence as i64;
+    let mut scanned = ScannedBlock::default();
+    for (hash, transaction) in transactions.iter() {
+        for spend in transaction.spends().iter() {
+            scanned.spends.push(SpentNullifier {
+                nullifier: hex::encode(spend.nullifier().0),
+                tx_hash: hash.clone(),
+                sequence,
+                block_hash: block.hash.clone(),
+            });
+        }
+        for output in transaction.outputs().iter() {
+            let merkle_note = output.merkle_note();
+            for key in keys.iter() {
+                if let Ok(note) = merkle_note.decrypt_note_for_owner(&key.incoming) {
+                    let nullifier = hex::encode(note.nullifier(&key.view, position).0);
+                    scanned.notes.extend(to_decrypted_note(
+                        key,
+                        &note,
+                        hash,
+                        position,
+                        block,
+                        Some(nullifier),
+                    ));
+                } else if let Ok(note) = merkle_note.decrypt_note_for_spender(&key.outgoing) {
+                    scanned
+                        .notes
+                        .extend(to_decrypted_note(key, &note, hash, position, block, None));
+                }
+            }
+            position += 1;
+        }
+    }
+    Ok(scanned)
+}
+
+fn to_decrypted_note(
+    key: &AccountKeys,
+    note: &Note,
+    tx_hash: &str,
+    position: u64,
+    block: &RpcBlock,
+    nullifier: Option<String>,
+) -> Option<DecryptedNote> {
+    // values are stored as BIGINT, skip the ones which would wrap rather than the whole block
+    let Ok(value) = i64::try_from(note.value()) else {
+        warn!(
+            "Note {} of {} at position {} has a value above i64, skipped",
+            hex::encode(note.commitment()),
+            key.address,
+            position
+        );
+        return None;
+    };
+    Some(DecryptedNote {
+        address: key.address.clone(),
+        commitment: hex::encode(note.commitment()),
+        tx_hash: tx_hash.to_string(),
+        position: position as i64,
+        sequence: block.sequence as i64,
+        block_hash: block.hash.clone(),
+        asset_id: hex::encode(note.asset_id().as_bytes()),
+        value,
+        memo: String::from_utf8_lossy(&note.memo().0)
+            .trim_end_matches('\0')
+            .to_string(),
+        sender: note.sender().hex_public_address(),
+        owner: note.owner().hex_public_address(),
+        nullifier,
+        spent_tx: None,
+        spent_sequence: None,
+    })
+}
@@ -0,0 +1,305 @@
+mod decrypt;
+mod reorg;
+mod worker;
+
+pub use decrypt::*;
+pub use reorg::*;
+pub use worker::*;
+
+use std::{collections::HashMap, sync::Arc};
+
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::ScannerConfig,
+    db_handler::{Account, PgHandler, WorkerStatus},
+    error::OreoError,
+    rpc_handler::RpcPool,
+};
+
+/// Spawn the scanner workers of this process as configured
+pub fn spawn_workers(
+    db_handler: PgHandler,
+    rpc_pool: RpcPool,
+    config: ScannerConfig,
+) -> Vec<JoinHandle<()>> {
+    config
+        .worker_ids()
+        .into_iter()
+        .map(|worker_id| {
+            Scanner::new(
+                db_handler.clone(),
+                rpc_pool.clone(),
+                config.clone(),
+                worker_id as i32,
+            )
+            .spawn()
+        })
+        .collect()
+}
+
+/// Scans blocks from the node and decrypts notes of the accounts owned by one worker server side
+pub struct Scanner {
+    pub db_handler: PgHandler,
+    pub rpc_pool: RpcPool,
+    pub config: ScannerConfig,
+    pub worker_id: i32,
+    progress: watch::Sender<WorkerStatus>,
+    keys: HashMap<String, AccountKeys>,
+}
+
+impl Scanner {
+    pub fn new(
+        db_handler: PgHandler,
+        rpc_pool: RpcPool,
+        config: ScannerConfig,
+        worker_id: i32,
+    ) -> Self {
+        Self {
+            db_handler,
+            rpc_pool,
+            config,
+            worker_id,
+            progress: watch::Sender::new(WorkerStatus {
+                id: worker_id,
+                head: 0,
+                accounts: 0,
+                updated_at: 0,
+            }),
+            keys: HashMap::new(),
+        }
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(self.run())
+    }
+
+    pub async fn run(mut self) {
+        info!("Scanner worker {} started", self.worker_id);
+        tokio::spawn(heartbeat(
+            self.db_handler.clone(),
+            self.progress.subscribe(),
+            self.config.worker_timeout,
+        ));
+        let mut ticker = tokio::time::interval(self.config.interval());
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.coordinate().await {
+                warn!(
+                    "Scanner worker {} failed to coordinate: {}",
+                    self.worker_id, e
+                );
+            }
+            match self.scan_once().await {
+                Ok(0) => {}
+                Ok(scanned) => debug!(
+                    "Scanner worker {} processed {} blocks",
+                    self.worker_id, scanned
+                ),
+                Err(e) => warn!("Scanner worker {} round failed: {}", self.worker_id, e),
+            }
+        }
+    }
+
+    /// On the lowest live worker, rebalance accounts across live workers
+    pub async fn coordinate(&mut self) -> Result<(), OreoError> {
+        let now = unix_now();
+        let live = self
+            .db_handler
+            .get_live_workers(now - self.config.worker_timeout as i64)
+            .await
+            .map_err(|_| OreoError::DBError)?;
+        if live.first() == Some(&self.worker_id) {
+            rebalance(&self.db_handler, &live).await?;
+        }
+        Ok(())
+    }
+
+    /// Scan the tip for accounts within `batch` blocks of it, then up to `batch` blocks above
+    /// the lowest head of the ones catching up, so a lagging account never holds back the
+    /// synced ones, returns the number of blocks
+    pub async fn scan_once(&mut self) -> Result<u64, OreoError> {
+        let mut accounts = self.accounts().await?;
+        self.progress
+            .send_modify(|progress| progress.accounts = accounts.len() as i64);
+        if accounts.is_empty() {
+            return Ok(0);
+        }
+        let (_, rpc_handler) = self.rpc_pool.any()?;
+        let chain_head = rpc_handler
+            .get_latest_block()
+            .await?
+            .data
+            .current_block_identifier
+            .index
+            .parse::<i64>()
+            .map_err(|e| OreoError::ParseError(e.to_string()))?;
+        // synced accounts first, they can reach the tip this round
+        let batch = self.config.batch as i64;
+        accounts.sort_by_key(|account| account.head + batch < chain_head);
+        let synced = accounts
+            .iter()
+            .filter(|account| account.head + batch >= chain_head)
+            .count();
+        let mut scanned = 0;
+        for group in [0..synced, synced..accounts.len()] {
+            if group.is_empty() {
+                continue;
+            }
+            scanned += self.scan_range(&mut accounts[group], chain_head).await?;
+            let head = accounts.iter().map(|account| account.head).min().unwrap();
+            self.progress.send_modify(|progress| progress.head = head);
+        }
+        Ok(scanned)
+    }
+
+    /// Scan up to `batch` blocks above the lowest head of `accounts`, moving their heads
+    async fn scan_range(
+        &mut self,
+        accounts: &mut [Account],
+        chain_head: i64,
+    ) -> Result<u64, OreoError> {
+        let (_, rpc_handler) = self.rpc_pool.any()?;
+        let start = accounts.iter().map(|account| account.head).min().unwrap() + 1;
+        let end = chain_head.min(start + self.config.batch as i64 - 1);
+        if self.check_heads(accounts, end, chain_head).await? {
+            return Ok(0);
+        }
+        let mut scanned = 0;
+        for sequence in start..=end {
+            let block = rpc_handler
+                .get_full_block(sequence as u64)
+                .await?
+                .data
+                .block;
+            if accounts.iter().any(|account| is_forked(account, &block)) {
+                self.handle_reorg(accounts, sequence - 1).await?;
+                break;
+            }
+            let behind: Vec<&mut Account> = accounts
+                .iter_mut()
+                .filter(|account| account.head < sequence)
+                .collect();
+            let keys: Arc<Vec<AccountKeys>> = Arc::new(
+                behind
+                    .iter()
+                    .filter_map(|account| self.keys.get(&account.address).cloned())
+                    .collect(),
+            );
+            let result = {
+                let block = block.clone();
+                tokio::task::spawn_blocking(move || decrypt_block(&block, &keys))
+                    .await
+                    .map_err(|e| OreoError::ParseError(e.to_string()))??
+            };
+            let addresses: Vec<String> = behind
+                .iter()
+                .map(|account| account.address.clone())
+                .collect();
+            self.db_handler
+                .save_scanned_block(
+                    self.worker_id,
+                    sequence,
+                    block.hash.clone(),
+                    &addresses,
+                    &result.notes,
+                    &result.spends,
+                )
+                .await
+                .map_err(|_| OreoError::DBError)?;
+            for account in behind {
+                account.head = sequence;
+                account.hash = block.hash.clone();
+            }
+            if !result.notes.is_empty() {
+                info!(
+                    "Scanner found {} notes in block {}",
+                    result.notes.len(),
+                    sequence
+                );
+            }
+            scanned += 1;
+        }
+        Ok(scanned)
+    }
+
+    /// Roll back accounts this round won't scan past when their head left the canonical chain,
+    /// returns whether a reorg was handled
+    async fn check_heads(
+        &mut self,
+        accounts: &[Account],
+        end: i64,
+        chain_head: i64,
+    ) -> Result<bool, OreoError> {
+        let (_, rpc_handler) = self.rpc_pool.any()?;
+        for (head, hash) in heads_to_check(accounts, end) {
+            if head > chain_head {
+                // the canonical chain got shorter than what was scanned
+                self.handle_reorg(accounts, chain_head).await?;
+                return Ok(true);
+            }
+            if !canonical_hash(&rpc_handler, head)
+                .await?
+                .eq_ignore_ascii_case(&hash)
+            {
+                self.handle_reorg(accounts, head - 1).await?;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Roll owned accounts back to the fork point below `sequence`, the next round rescans
+    /// the canonical chain from there
+    async fn handle_reorg(&mut self, accounts: &[Account], sequence: i64) -> Result<(), OreoError> {
+        let (_, rpc_handler) = self.rpc_pool.any()?;
+        let (fork, hash) =
+            find_fork(&self.db_handler, &rpc_handler, self.worker_id, sequence).await?;
+        let addresses: Vec<String> = accounts
+            .iter()
+            .map(|account| account.address.clone())
+            .collect();
+        let events = self
+            .db_handler
+            .rollback(self.worker_id, &addresses, fork, hash, unix_now())
+            .await
+            .map_err(|_| OreoError::DBError)?;
+        warn!(
+            "Scanner worker {} rolled {} accounts back to block {} on reorg",
+            self.worker_id,
+            events.len(),
+            fork
+        );
+        self.progress
+            .send_modify(|progress| progress.head = progress.head.min(fork));
+        Ok(())
+    }
+
+    /// Owned accounts with valid view keys, keys are parsed once and cached
+    async fn accounts(&mut self) -> Result<Vec<Account>, OreoError> {
+        let accounts = self
+            .db_handler
+            .get_by_worker(self.worker_id)
+            .await
+            .map_err(|_| OreoError::DBError)?;
+        self.keys
+            .retain(|address, _| accounts.iter().any(|account| &account.address == address));
+        let mut valid = vec![];
+        for account in accounts.into_iter() {
+            if !self.keys.contains_key(&account.address) {
+                match AccountKeys::from_account(&account) {
+                    Ok(keys) => {
+                        self.keys.insert(account.address.clone(), keys);
+                    }
+                    Err(e) => {
+                        warn!("Skip account with invalid view keys: {}", e);
+                        continue;
+                    }
+                }
+            }
+            valid.push(account);
+        }
+        Ok(valid)
+    }
+}
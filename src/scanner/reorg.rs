@@ -0,0 +1,122 @@
+use crate::{
+    db_handler::{Account, PgHandler},
+    error::OreoError,
+    rpc_handler::{
+        abi::{GetBlockReq, RpcBlock},
+        RpcHandler,
+    },
+};
+
+/// Whether `block` doesn't extend the head the account has scanned to
+pub fn is_forked(account: &Account, block: &RpcBlock) -> bool {
+    account.head + 1 == block.sequence as i64
+        && !account.hash.is_empty()
+        && !account
+            .hash
+            .eq_ignore_ascii_case(&block.previous_block_hash)
+}
+
+/// Heads at or above `from` with their scanned hash, a reorg at the tip or to a shorter chain
+/// leaves these accounts with nothing to scan so their head is checked as is
+pub fn heads_to_check(accounts: &[Account], from: i64) -> Vec<(i64, String)> {
+    let mut heads: Vec<(i64, String)> = accounts
+        .iter()
+        .filter(|account| account.head >= from && !account.hash.is_empty())
+        .map(|account| (account.head, account.hash.to_lowercase()))
+        .collect();
+    heads.sort();
+    heads.dedup();
+    heads
+}
+
+/// Hash of the block at `sequence` on the node's canonical chain
+pub async fn canonical_hash(rpc_handler: &RpcHandler, sequence: i64) -> Result<String, OreoError> {
+    Ok(rpc_handler
+        .get_block(GetBlockReq {
+            hash: None,
+            sequence: Some(sequence as u64),
+            serialized: None,
+        })
+        .await?
+        .data
+        .block
+        .hash)
+}
+
+/// Walk back from `sequence` to the highest block whose hash scanned by `worker_id` is still
+/// canonical, blocks never scanned are taken as the fork point
+pub async fn find_fork(
+    db_handler: &PgHandler,
+    rpc_handler: &RpcHandler,
+    worker_id: i32,
+    mut sequence: i64,
+) -> Result<(i64, String), OreoError> {
+    loop {
+        let canonical = canonical_hash(rpc_handler, sequence).await?;
+        let scanned = db_handler
+            .get_block_hash(worker_id, sequence)
+            .await
+            .map_err(|_| OreoError::DBError)?;
+        match scanned {
+            Some(hash) if !hash.eq_ignore_ascii_case(&canonical) && sequence > 1 => sequence -= 1,
+            _ => return Ok((sequence, canonical)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{constants::Network, db_handler::Account, rpc_handler::abi::RpcBlock};
+
+    use super::{heads_to_check, is_forked};
+
+    fn account(head: i64, hash: &str) -> Account {
+        Account {
+            name: "test".to_string(),
+            create_head: Some(1),
+            create_hash: Some("aa".repeat(32)),
+            head,
+            hash: hash.to_string(),
+            in_vk: "".to_string(),
+            out_vk: "".to_string(),
+            vk: "".to_string(),
+            address: "".to_string(),
+            network_id: Network::Mainnet.id(),
+            worker_id: 1,
+        }
+    }
+
+    #[test]
+    fn fork_should_be_detected() {
+        let account = account(5, &"bb".repeat(32));
+        let mut block = RpcBlock {
+            hash: "cc".repeat(32),
+            sequence: 6,
+            previous_block_hash: "BB".repeat(32),
+            timestamp: 0,
+            note_size: None,
+            transactions: vec![],
+        };
+        assert!(!is_forked(&account, &block));
+        block.previous_block_hash = "dd".repeat(32);
+        assert!(is_forked(&account, &block));
+        block.sequence = 7;
+        assert!(!is_forked(&account, &block));
+    }
+
+    #[test]
+    fn heads_above_the_batch_should_be_checked() {
+        let accounts = vec![
+            account(5, &"BB".repeat(32)),
+            account(9, &"cc".repeat(32)),
+            account(5, &"bb".repeat(32)),
+            account(7, ""),
+            account(3, &"dd".repeat(32)),
+        ];
+        assert_eq!(
+            heads_to_check(&accounts, 5),
+            vec![(5, "bb".repeat(32)), (9, "cc".repeat(32))]
+        );
+        assert!(heads_to_check(&accounts, 10).is_empty());
+    }
+}
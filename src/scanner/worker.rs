@@ -0,0 +1,112 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+use crate::{
+    db_handler::{PgHandler, WorkerStatus},
+    error::OreoError,
+};
+
+/// Worker id of accounts not assigned to any scanner worker yet
+pub const UNASSIGNED_WORKER: i32 = 0;
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+fn weight(address: &str, worker_id: i32) -> u64 {
+    address
+        .as_bytes()
+        .iter()
+        .chain(worker_id.to_le_bytes().iter())
+        .fold(FNV_OFFSET, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+        })
+}
+
+/// Rendezvous hashing so only accounts of a leaving or joining worker move,
+/// every worker computes the same owner without coordination
+pub fn owner_of(address: &str, workers: &[i32]) -> Option<i32> {
+    workers
+        .iter()
+        .copied()
+        .max_by_key(|worker_id| (weight(address, *worker_id), *worker_id))
+}
+
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Report the progress of a worker three times per `worker_timeout` however long its rounds
+/// take and prune workers gone for longer than `worker_timeout`, ends with the worker
+pub async fn heartbeat(
+    db_handler: PgHandler,
+    progress: watch::Receiver<WorkerStatus>,
+    worker_timeout: u64,
+) {
+    let mut ticker = tokio::time::interval(Duration::from_secs((worker_timeout / 3).max(1)));
+    while progress.has_changed().is_ok() {
+        ticker.tick().await;
+        let now = unix_now();
+        let mut status = progress.borrow().clone();
+        status.updated_at = now;
+        if let Err(e) = db_handler.heartbeat(status).await {
+            warn!("Scanner worker heartbeat failed: {}", e);
+        }
+        match db_handler.prune_workers(now - worker_timeout as i64).await {
+            Ok(0) => {}
+            Ok(pruned) => info!("Pruned {} stale scanner workers", pruned),
+            Err(e) => warn!("Failed to prune stale scanner workers: {}", e),
+        }
+    }
+}
+
+/// Move accounts to their owner among live workers, returns the number of moved accounts
+pub async fn rebalance(db_handler: &PgHandler, workers: &[i32]) -> Result<usize, OreoError> {
+    let assignments = db_handler
+        .get_assignments()
+        .await
+        .map_err(|_| OreoError::DBError)?;
+    let mut moved = 0;
+    for (address, current) in assignments.into_iter() {
+        let owner = owner_of(&address, workers).unwrap_or(UNASSIGNED_WORKER);
+        if owner != current {
+            db_handler
+                .assign_worker(address, owner)
+                .await
+                .map_err(|_| OreoError::DBError)?;
+            moved += 1;
+        }
+    }
+    if moved > 0 {
+        info!("Rebalanced {} accounts across workers {:?}", moved, workers);
+    }
+    Ok(moved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::owner_of;
+
+    #[test]
+    fn owner_should_be_stable_when_worker_joins() {
+        let addresses: Vec<String> = (0..100).map(|x| format!("{:064x}", x)).collect();
+        let before: Vec<i32> = addresses
+            .iter()
+            .map(|x| owner_of(x, &[1, 2, 3]).unwrap())
+            .collect();
+        assert!([1, 2, 3].iter().all(|id| before.contains(id)));
+        for (address, owner) in addresses.iter().zip(before.iter()) {
+            let after = owner_of(address, &[1, 2, 3, 4]).unwrap();
+            assert!(after == *owner || after == 4);
+            let shrunk = owner_of(address, &[1, 3]).unwrap();
+            if *owner != 2 {
+                assert_eq!(shrunk, *owner);
+            }
+        }
+        assert_eq!(owner_of(&addresses[0], &[]), None);
+    }
+}
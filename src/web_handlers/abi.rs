@@ -1,12 +1,16 @@
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    constants::{MAINNET_GENESIS_HASH, MAINNET_GENESIS_SEQUENCE},
+    constants::NetworkProfile,
     db_handler::{address_to_name, Account},
     error::OreoError,
-    rpc_handler::abi::{
-        AssetBalanceDelta, CreateAccountOpt, GetAccountTransactionRep, RpcNote,
-        TransactionWithNotes,
+    prover::TransactionSecrets,
+    rpc_handler::{
+        abi::{
+            AssetBalanceDelta, CreateAccountOpt, CreateTxReq, GetAccountTransactionRep, RpcNote,
+            TransactionWithNotes,
+        },
+        RpcError,
     },
 };
 
@@ -21,22 +25,27 @@ pub struct ImportAccountReq {
 }
 
 impl ImportAccountReq {
-    pub fn to_account(&self) -> Account {
+    pub fn to_account(&self, network: &NetworkProfile) -> Result<Account, OreoError> {
         let (create_head, create_hash) = match &self.created_at {
-            Some(creat) => (Some(creat.sequence as i64), Some(creat.hash.clone())),
+            Some(creat) => {
+                network.check_created_at(creat.sequence, &creat.hash)?;
+                (Some(creat.sequence as i64), Some(creat.hash.clone()))
+            }
             None => (None, None),
         };
-        Account {
+        Ok(Account {
             address: self.public_address.clone(),
             name: address_to_name(&self.public_address),
             create_head,
             create_hash: create_hash.clone(),
-            head: create_head.unwrap_or(MAINNET_GENESIS_SEQUENCE),
-            hash: create_hash.unwrap_or(MAINNET_GENESIS_HASH.to_string()),
+            head: create_head.unwrap_or(network.genesis_sequence),
+            hash: create_hash.unwrap_or(network.genesis_hash.clone()),
             in_vk: self.incoming_view_key.clone(),
             out_vk: self.outgoing_view_key.clone(),
             vk: self.view_key.clone(),
-        }
+            network_id: network.network.id(),
+            worker_id: 0,
+        })
     }
 }
 
@@ -63,7 +72,13 @@ pub struct GenerateProofReq {
     pub mint_asset_circuits: Vec<Vec<u8>>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl GenerateProofReq {
+    pub fn circuits(&self) -> usize {
+        self.spend_circuits.len() + self.output_circuits.len() + self.mint_asset_circuits.len()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GenerateProofRep {
     pub spend_proofs: Vec<Vec<u8>>,
@@ -71,11 +86,59 @@ pub struct GenerateProofRep {
     pub mint_asset_proofs: Vec<Vec<u8>>,
 }
 
+/// Groth16 proof with its public inputs, each a 32 bytes little endian scalar
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProofWithInputs {
+    pub proof: Vec<u8>,
+    pub public_inputs: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyProofReq {
+    #[serde(default)]
+    pub spend_proofs: Vec<ProofWithInputs>,
+    #[serde(default)]
+    pub output_proofs: Vec<ProofWithInputs>,
+    #[serde(default)]
+    pub mint_asset_proofs: Vec<ProofWithInputs>,
+}
+
+impl VerifyProofReq {
+    pub fn proofs(&self) -> usize {
+        self.spend_proofs.len() + self.output_proofs.len() + self.mint_asset_proofs.len()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyProofRep {
+    /// All proofs are valid
+    pub valid: bool,
+    pub spend_proofs: Vec<bool>,
+    pub output_proofs: Vec<bool>,
+    pub mint_asset_proofs: Vec<bool>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RegisterWorkerReq {
+    /// Base url of the prover worker
+    pub url: String,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct GetAccountStatusReq {
     pub account: String,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GetReorgEventsReq {
+    pub account: String,
+    /// Only events with id greater than this one
+    pub since: Option<i64>,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct AccountStatus {
     pub name: String,
@@ -87,6 +150,87 @@ pub struct GetAccountStatusRep {
     pub account: AccountStatus,
 }
 
+/// `/createTx` request with the proof generation key of the account, hex of `ak` and `nsk`
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateProvenTxReq {
+    #[serde(flatten)]
+    pub transaction: CreateTxReq,
+    pub proof_generation_key: String,
+}
+
+/// Raw transaction with its proofs and the randomness they were built with, all as hex
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateProvenTxRep {
+    pub transaction: String,
+    pub public_key_randomness: String,
+    pub spends: Vec<ProvenSpend>,
+    /// Outputs of the transaction followed by the change
+    pub outputs: Vec<ProvenOutput>,
+    pub mints: Vec<ProvenMint>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProvenSpend {
+    pub proof: String,
+    pub value_commitment_randomness: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProvenOutput {
+    pub note: String,
+    pub proof: String,
+    pub value_commitment_randomness: String,
+    pub esk: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProvenMint {
+    pub proof: String,
+}
+
+impl CreateProvenTxRep {
+    pub fn new(transaction: String, secrets: TransactionSecrets, proofs: GenerateProofRep) -> Self {
+        Self {
+            transaction,
+            public_key_randomness: hex::encode(secrets.public_key_randomness.to_bytes()),
+            spends: secrets
+                .spends
+                .iter()
+                .zip(proofs.spend_proofs.iter())
+                .map(|(randomness, proof)| ProvenSpend {
+                    proof: hex::encode(proof),
+                    value_commitment_randomness: hex::encode(randomness.to_bytes()),
+                })
+                .collect(),
+            outputs: secrets
+                .outputs
+                .iter()
+                .zip(proofs.output_proofs.iter())
+                .map(|(output, proof)| ProvenOutput {
+                    note: hex::encode(output.note.to_bytes()),
+                    proof: hex::encode(proof),
+                    value_commitment_randomness: hex::encode(
+                        output.value_commitment_randomness.to_bytes(),
+                    ),
+                    esk: hex::encode(output.esk.to_bytes()),
+                })
+                .collect(),
+            mints: proofs
+                .mint_asset_proofs
+                .iter()
+                .map(|proof| ProvenMint {
+                    proof: hex::encode(proof),
+                })
+                .collect(),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct BlockIdentifier {
     pub index: String,
@@ -162,7 +306,11 @@ impl TransactionDetail {
                 memo: Some(memo.into()),
                 value: value.into(),
             }),
-            None => Err(OreoError::InternalRpcError),
+            None => Err(OreoError::InternalRpcError(RpcError {
+                code: "missing-notes".to_string(),
+                status: 200,
+                message: format!("transaction {} has no note", hash),
+            })),
         }
     }
 }
@@ -190,3 +338,53 @@ impl GetTransactionDetail {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        constants::{Network, NetworkProfile, MAINNET_GENESIS_HASH},
+        error::OreoError,
+        rpc_handler::abi::CreateAccountOpt,
+    };
+
+    use super::ImportAccountReq;
+
+    fn import_req(created_at: Option<CreateAccountOpt>) -> ImportAccountReq {
+        ImportAccountReq {
+            view_key: "4ae4eb9606ba57b3b17a444100a9ac6453cd67e6fe4c860e63a2e18b1200978ab5ecce68e8639d5016cbe73b0ea9a3c8e906fc881af2e9ccfa7a7b63fb73d555".to_string(),
+            incoming_view_key: "4a08bec0ec5a471352f340d737e4b3baec2aec8d0a2e12201d92d8ad71aadd07".to_string(),
+            outgoing_view_key: "cee4ff41d7d8da5eedc6493134981eaad7b26a8b0291a4eac9ba95090fa47bf7".to_string(),
+            public_address: "d63ba13d7c35caf942c64d5139b948b885ec931977a3f248c13e7f3c1bd0aa64".to_string(),
+            created_at,
+        }
+    }
+
+    #[test]
+    fn to_account_should_use_network_genesis() {
+        let testnet = NetworkProfile::new(Network::Testnet, Some("11".repeat(32))).unwrap();
+        let account = import_req(None).to_account(&testnet).unwrap();
+        assert_eq!(account.hash, "11".repeat(32));
+        assert_eq!(account.head, 1);
+        assert_eq!(account.network_id, Network::Testnet.id());
+    }
+
+    #[test]
+    fn to_account_should_reject_foreign_genesis() {
+        let testnet = NetworkProfile::new(Network::Testnet, Some("11".repeat(32))).unwrap();
+        let created_at = CreateAccountOpt {
+            hash: MAINNET_GENESIS_HASH.to_string(),
+            sequence: 1,
+        };
+        let result = import_req(Some(created_at)).to_account(&testnet);
+        assert_eq!(
+            result.unwrap_err(),
+            OreoError::NetworkMismatch(MAINNET_GENESIS_HASH.to_string())
+        );
+        let mainnet = NetworkProfile::mainnet();
+        let created_at = CreateAccountOpt {
+            hash: MAINNET_GENESIS_HASH.to_string(),
+            sequence: 1,
+        };
+        assert!(import_req(Some(created_at)).to_account(&mainnet).is_ok());
+    }
+}
@@ -1,30 +1,61 @@
 use axum::{
     extract::{self, State},
     response::IntoResponse,
+    Json,
 };
+use serde_json::json;
 
 use crate::{
     constants::ACCOUNT_VERSION,
-    db_handler::DBHandler,
+    db_handler::{local_balances, Account, DBHandler},
     error::OreoError,
+    prover::{proof_generation_key, prove_cached, Admission, ProvingPool, RawTransaction},
     rpc_handler::abi::{
-        BroadcastTxReq, CreateTxReq, GetAccountTransactionReq, GetBalancesRep, GetBalancesReq,
-        GetNoteWitnessReq, GetTransactionsReq, ImportAccountReq as RpcImportReq, OutPut,
-        RemoveAccountReq as RpcRemoveAccountReq, RpcResponse,
+        BroadcastTxReq, CreateTxRep, CreateTxReq, GetAccountTransactionReq, GetBalancesRep,
+        GetBalancesReq, GetBlockReq, GetNoteWitnessReq, GetTransactionsReq,
+        ImportAccountReq as RpcImportReq, OutPut, RemoveAccountReq as RpcRemoveAccountReq,
+        RpcResponse,
     },
     SharedState,
 };
 
-use super::abi::{GetAccountStatusReq, GetTransactionDetail, ImportAccountReq, RemoveAccountReq};
+use super::abi::{
+    CreateProvenTxRep, CreateProvenTxReq, GetAccountStatusReq, GetReorgEventsReq,
+    GetTransactionDetail, ImportAccountReq, RemoveAccountReq,
+};
 
 pub async fn import_vk_handler<T: DBHandler>(
     State(shared): State<SharedState<T>>,
     extract::Json(import): extract::Json<ImportAccountReq>,
 ) -> impl IntoResponse {
-    let account_name = shared
-        .db_handler
-        .save_account(import.clone().to_account(), 0)
-        .await;
+    let account = match import.to_account(&shared.network) {
+        Ok(account) => account,
+        Err(e) => return e.into_response(),
+    };
+    let (node, rpc_handler) = match shared.rpc_pool.any() {
+        Ok(node) => node,
+        Err(e) => return e.into_response(),
+    };
+    if let Some(created_at) = &import.created_at {
+        if !shared.network.is_genesis(created_at.sequence as i64) {
+            let block = rpc_handler
+                .get_block(GetBlockReq {
+                    hash: Some(created_at.hash.clone()),
+                    sequence: None,
+                    serialized: None,
+                })
+                .await;
+            match block {
+                Ok(block) if block.data.block.sequence == created_at.sequence => {}
+                // a hash unknown to the node belongs to another network
+                Ok(_) | Err(OreoError::NodeNotFound(_)) | Err(OreoError::NodeValidation(_)) => {
+                    return OreoError::NetworkMismatch(created_at.hash.clone()).into_response()
+                }
+                Err(e) => return e.into_response(),
+            }
+        }
+    }
+    let account_name = shared.db_handler.save_account(account, 0).await;
     if let Err(e) = account_name {
         return e.into_response();
     }
@@ -35,20 +66,29 @@ pub async fn import_vk_handler<T: DBHandler>(
         public_address,
         created_at,
     } = import;
+    let account_name = account_name.unwrap();
+    let address = public_address.clone();
     let rpc_data = RpcImportReq {
         view_key,
         incoming_view_key,
         outgoing_view_key,
         public_address,
         version: ACCOUNT_VERSION,
-        name: account_name.unwrap(),
+        name: account_name.clone(),
         created_at,
     };
-    shared
-        .rpc_handler
+    let result = rpc_handler
         .import_view_only(rpc_data)
         .await
-        .into_response()
+        .map_err(|e| match e {
+            // the node names accounts after their address
+            OreoError::NodeAccountExists(_) => OreoError::Duplicate(address),
+            e => e,
+        });
+    if result.is_ok() {
+        shared.rpc_pool.pin(&account_name, node);
+    }
+    result.into_response()
 }
 
 pub async fn remove_account_handler<T: DBHandler>(
@@ -62,10 +102,14 @@ pub async fn remove_account_handler<T: DBHandler>(
     if let Err(e) = db_account {
         return e.into_response();
     }
-    let result = shared
-        .rpc_handler
+    let account_name = db_account.unwrap().name;
+    let rpc_handler = match shared.rpc_pool.for_account(&account_name).await {
+        Ok(handler) => handler,
+        Err(e) => return e.into_response(),
+    };
+    let result = rpc_handler
         .remove_account(RpcRemoveAccountReq {
-            account: db_account.unwrap().name,
+            account: account_name.clone(),
             confirm: Some(true),
             wait: Some(true),
         })
@@ -79,6 +123,7 @@ pub async fn remove_account_handler<T: DBHandler>(
             {
                 return e.into_response();
             }
+            shared.rpc_pool.unpin(&account_name);
             response.into_response()
         }
         Err(e) => e.into_response(),
@@ -96,18 +141,14 @@ pub async fn get_balances_handler<T: DBHandler>(
     if let Err(e) = db_account {
         return e.into_response();
     }
-    let resp = shared
-        .rpc_handler
-        .get_balance(GetBalancesReq {
-            account: db_account.unwrap().name,
-            confirmations: Some(get_balance.confirmations.unwrap_or(10)),
-        })
-        .await;
-    match resp {
+    let confirmations = get_balance
+        .confirmations
+        .unwrap_or(shared.defaults.confirmations);
+    match account_balances(&shared, db_account.unwrap(), confirmations).await {
         Ok(res) => {
             let response = RpcResponse {
                 status: 200,
-                data: GetBalancesRep::verified_asset(res.data),
+                data: GetBalancesRep::verified_asset(res),
             };
             response.into_response()
         }
@@ -126,18 +167,14 @@ pub async fn get_ores_handler<T: DBHandler>(
     if let Err(e) = db_account {
         return e.into_response();
     }
-    let resp = shared
-        .rpc_handler
-        .get_balance(GetBalancesReq {
-            account: db_account.unwrap().name,
-            confirmations: Some(get_balance.confirmations.unwrap_or(10)),
-        })
-        .await;
-    match resp {
+    let confirmations = get_balance
+        .confirmations
+        .unwrap_or(shared.defaults.confirmations);
+    match account_balances(&shared, db_account.unwrap(), confirmations).await {
         Ok(res) => {
             let response = RpcResponse {
                 status: 200,
-                data: GetBalancesRep::ores(res.data).await,
+                data: GetBalancesRep::ores(res, &shared.orescriptions_endpoint).await,
             };
             response.into_response()
         }
@@ -145,6 +182,45 @@ pub async fn get_ores_handler<T: DBHandler>(
     }
 }
 
+/// Balances from notes stored by the scanner when enabled, otherwise from the node's wallet
+async fn account_balances<T: DBHandler>(
+    shared: &SharedState<T>,
+    account: Account,
+    confirmations: u32,
+) -> Result<GetBalancesRep, OreoError> {
+    if !shared.local_balances {
+        let rpc_handler = shared.rpc_pool.for_account(&account.name).await?;
+        let balances = rpc_handler
+            .get_balance(GetBalancesReq {
+                account: account.name,
+                confirmations: Some(confirmations),
+            })
+            .await?;
+        return Ok(balances.data);
+    }
+    let notes = shared
+        .db_handler
+        .get_owned_notes(account.address, account.head - confirmations as i64)
+        .await?;
+    let balances = local_balances(
+        &notes,
+        account.head,
+        confirmations as i64,
+        &shared.network.native_asset,
+    );
+    let (_, rpc_handler) = shared.rpc_pool.any()?;
+    let mut assets = vec![];
+    for balance in balances.iter() {
+        assets.push(rpc_handler.get_asset(balance.asset_id.clone()).await?.data);
+    }
+    Ok(GetBalancesRep::from_local(
+        account.name,
+        balances,
+        &assets,
+        account.head as u64,
+    ))
+}
+
 pub async fn get_transactions_handler<T: DBHandler>(
     State(shared): State<SharedState<T>>,
     extract::Json(get_transactions): extract::Json<GetTransactionsReq>,
@@ -156,11 +232,19 @@ pub async fn get_transactions_handler<T: DBHandler>(
     if let Err(e) = db_account {
         return e.into_response();
     }
-    shared
-        .rpc_handler
+    let account_name = db_account.unwrap().name;
+    let rpc_handler = match shared.rpc_pool.for_account(&account_name).await {
+        Ok(handler) => handler,
+        Err(e) => return e.into_response(),
+    };
+    rpc_handler
         .get_transactions(GetTransactionsReq {
-            account: db_account.unwrap().name,
-            limit: Some(get_transactions.limit.unwrap_or(6)),
+            account: account_name,
+            limit: Some(
+                get_transactions
+                    .limit
+                    .unwrap_or(shared.defaults.transactions_limit),
+            ),
             reverse: Some(true),
         })
         .await
@@ -169,50 +253,106 @@ pub async fn get_transactions_handler<T: DBHandler>(
 
 pub async fn create_transaction_handler<T: DBHandler>(
     State(shared): State<SharedState<T>>,
-    extract::Json(create_transaction): extract::Json<CreateTxReq>,
+    extract::Json(request): extract::Json<CreateTxReq>,
 ) -> impl IntoResponse {
-    let db_account = shared
-        .db_handler
-        .get_account(create_transaction.account.clone())
-        .await;
-    if let Err(e) = db_account {
-        return e.into_response();
+    create_transaction(&shared, request)
+        .await
+        .map(|(_, transaction)| transaction)
+        .into_response()
+}
+
+pub async fn create_proven_transaction_handler<T: DBHandler>(
+    State(shared): State<SharedState<T>>,
+    extract::Json(request): extract::Json<CreateProvenTxReq>,
+) -> impl IntoResponse {
+    let Some(pool) = shared.pool.as_ref() else {
+        return OreoError::ProverNotReady.into_response();
+    };
+    let CreateProvenTxReq {
+        transaction,
+        proof_generation_key: key,
+    } = request;
+    let (account, raw) = match create_transaction(&shared, transaction).await {
+        Ok((account, raw)) => (account, raw.data.transaction),
+        Err(e) => return e.into_response(),
+    };
+    let result = prove_transaction(pool, &account, &key, &raw).await;
+    match result {
+        Ok(proven) => Json(json!({"code": 200, "data": proven})).into_response(),
+        Err(e) => e.into_response(),
     }
-    let outputs: Vec<OutPut> = create_transaction
+}
+
+/// Create the raw transaction of `request` on the node of its account
+async fn create_transaction<T: DBHandler>(
+    shared: &SharedState<T>,
+    request: CreateTxReq,
+) -> Result<(Account, RpcResponse<CreateTxRep>), OreoError> {
+    let account = shared
+        .db_handler
+        .get_account(request.account.clone())
+        .await?;
+    let rpc_handler = shared.rpc_pool.for_account(&account.name).await?;
+    let outputs: Vec<OutPut> = request
         .outputs
         .unwrap_or(vec![])
         .iter()
         .map(|output| OutPut::from(output.clone()))
         .collect();
     let mut mints = vec![];
-    for item in create_transaction.mints.unwrap_or(vec![]).into_iter() {
+    for item in request.mints.unwrap_or(vec![]).into_iter() {
         if item.asset_id.is_none() && item.name.is_none() {
-            return OreoError::BadMintRequest.into_response();
+            return Err(OreoError::BadMintRequest);
         } else {
             mints.push(item);
         }
     }
-    let burns = create_transaction.burns.unwrap_or(vec![]);
-    shared
-        .rpc_handler
+    let burns = request.burns.unwrap_or(vec![]);
+    let transaction = rpc_handler
         .create_transaction(CreateTxReq {
-            account: db_account.unwrap().name,
+            account: account.name.clone(),
             outputs: Some(outputs),
-            fee: Some(create_transaction.fee.unwrap_or("1".into())),
-            expiration_delta: Some(create_transaction.expiration_delta.unwrap_or(30)),
+            fee: Some(request.fee.unwrap_or(shared.defaults.fee.clone())),
+            expiration_delta: Some(
+                request
+                    .expiration_delta
+                    .unwrap_or(shared.defaults.expiration_delta),
+            ),
             mints: Some(mints),
             burns: Some(burns),
         })
-        .await
-        .into_response()
+        .await?;
+    Ok((account, transaction))
+}
+
+/// Build the circuits of the raw transaction `raw` with the proof generation key of
+/// `account` and prove them on `pool`
+async fn prove_transaction(
+    pool: &ProvingPool,
+    account: &Account,
+    key: &str,
+    raw: &str,
+) -> Result<CreateProvenTxRep, OreoError> {
+    let key = proof_generation_key(key, &account.vk)?;
+    let mut address = [0; 32];
+    hex::decode_to_slice(&account.address, &mut address)
+        .map_err(|e| OreoError::ParseError(e.to_string()))?;
+    let bytes = hex::decode(raw).map_err(|e| OreoError::ParseError(e.to_string()))?;
+    let transaction = RawTransaction::read(&bytes)?;
+    let (request, secrets) = transaction.circuits(&key, address)?;
+    let proofs = prove_cached(pool, None, request, false, Admission::Try, None).await?;
+    Ok(CreateProvenTxRep::new(raw.to_string(), secrets, proofs))
 }
 
 pub async fn broadcast_transaction_handler<T: DBHandler>(
     State(shared): State<SharedState<T>>,
     extract::Json(broadcast_transaction): extract::Json<BroadcastTxReq>,
 ) -> impl IntoResponse {
-    shared
-        .rpc_handler
+    let (_, rpc_handler) = match shared.rpc_pool.any() {
+        Ok(node) => node,
+        Err(e) => return e.into_response(),
+    };
+    rpc_handler
         .broadcast_transaction(broadcast_transaction)
         .await
         .into_response()
@@ -226,10 +366,14 @@ pub async fn account_status_handler<T: DBHandler>(
     if let Err(e) = db_account {
         return e.into_response();
     }
-    shared
-        .rpc_handler
+    let account_name = db_account.unwrap().name;
+    let rpc_handler = match shared.rpc_pool.for_account(&account_name).await {
+        Ok(handler) => handler,
+        Err(e) => return e.into_response(),
+    };
+    rpc_handler
         .get_account_status(GetAccountStatusReq {
-            account: db_account.unwrap().name,
+            account: account_name,
         })
         .await
         .into_response()
@@ -238,18 +382,22 @@ pub async fn account_status_handler<T: DBHandler>(
 pub async fn latest_block_handler<T: DBHandler>(
     State(shared): State<SharedState<T>>,
 ) -> impl IntoResponse {
-    shared.rpc_handler.get_latest_block().await.into_response()
+    let (_, rpc_handler) = match shared.rpc_pool.any() {
+        Ok(node) => node,
+        Err(e) => return e.into_response(),
+    };
+    rpc_handler.get_latest_block().await.into_response()
 }
 
 pub async fn note_witness_handler<T: DBHandler>(
     State(shared): State<SharedState<T>>,
     extract::Json(req): extract::Json<GetNoteWitnessReq>,
 ) -> impl IntoResponse {
-    shared
-        .rpc_handler
-        .get_note_witness(req)
-        .await
-        .into_response()
+    let (_, rpc_handler) = match shared.rpc_pool.any() {
+        Ok(node) => node,
+        Err(e) => return e.into_response(),
+    };
+    rpc_handler.get_note_witness(req).await.into_response()
 }
 
 pub async fn account_transaction_handler<T: DBHandler>(
@@ -260,10 +408,14 @@ pub async fn account_transaction_handler<T: DBHandler>(
     if let Err(e) = db_account {
         return e.into_response();
     }
-    let rpc_transaction = shared
-        .rpc_handler
+    let account_name = db_account.unwrap().name;
+    let rpc_handler = match shared.rpc_pool.for_account(&account_name).await {
+        Ok(handler) => handler,
+        Err(e) => return e.into_response(),
+    };
+    let rpc_transaction = rpc_handler
         .get_account_transaction(GetAccountTransactionReq {
-            account: db_account.unwrap().name,
+            account: account_name,
             hash: account.hash,
             notes: Some(true),
         })
@@ -283,3 +435,39 @@ pub async fn account_transaction_handler<T: DBHandler>(
         Err(e) => e.into_response(),
     }
 }
+
+pub async fn scanner_status_handler<T: DBHandler>(
+    State(shared): State<SharedState<T>>,
+) -> impl IntoResponse {
+    match shared.db_handler.get_worker_statuses().await {
+        Ok(workers) => RpcResponse {
+            status: 200,
+            data: workers,
+        }
+        .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn reorg_events_handler<T: DBHandler>(
+    State(shared): State<SharedState<T>>,
+    extract::Json(req): extract::Json<GetReorgEventsReq>,
+) -> impl IntoResponse {
+    let db_account = shared.db_handler.get_account(req.account.clone()).await;
+    if let Err(e) = db_account {
+        return e.into_response();
+    }
+    let address = db_account.unwrap().address;
+    match shared
+        .db_handler
+        .get_reorgs(address, req.since.unwrap_or(0))
+        .await
+    {
+        Ok(events) => RpcResponse {
+            status: 200,
+            data: events,
+        }
+        .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
@@ -1,127 +1,436 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::Instant,
+};
 
-use axum::{extract, response::IntoResponse, Json};
-use bellperson::groth16;
-use ironfish_rust::sapling_bls12::SAPLING;
+use axum::{
+    body::Bytes,
+    extract::{self, Path, Request, State},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::{
+        sse::{KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Extension, Json,
+};
+use bellperson::{groth16, Circuit};
+use blstrs::{Bls12, Scalar};
 use ironfish_zkp::proofs::{MintAsset, Output, Spend};
 use rand::thread_rng;
-use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator,
+};
 use serde_json::json;
 use tracing::{error, info};
 
-use crate::error::OreoError;
+use crate::{
+    error::{CircuitFailure, CircuitKind, FailureReason, OreoError},
+    prover::{
+        metrics, prove_cached, prover_params, self_check, stream_proofs, Admission, ClientPermit,
+        Coordinator, ProofEncoding, ProverState,
+    },
+};
 
-use super::abi::{GenerateProofRep, GenerateProofReq};
+use super::abi::{
+    GenerateProofRep, GenerateProofReq, ProofWithInputs, RegisterWorkerReq, VerifyProofRep,
+    VerifyProofReq,
+};
 
 pub async fn generate_proof_handler(
-    extract::Json(request): extract::Json<GenerateProofReq>,
+    State(prover): State<ProverState>,
+    headers: HeaderMap,
+    body: Bytes,
 ) -> impl IntoResponse {
     info!("calling generate_proof_handler");
-    let failed_index = Arc::new(Mutex::new(0u32));
-    let spend_proofs = request
-        .spend_circuits
-        .par_iter()
-        .enumerate()
-        .map(|(idx, bytes)| {
-            let proof_bytes = if let Ok(spend) = Spend::read(&bytes[..]) {
-                let proof =
-                    groth16::create_random_proof(spend, &SAPLING.spend_params, &mut thread_rng());
-                if let Ok(proof) = proof {
-                    let mut writer = vec![];
-                    proof.write(&mut writer).unwrap();
-                    Some(writer)
-                } else {
-                    None
-                }
-            } else {
-                None
-            };
-            if proof_bytes.is_none() {
-                *failed_index.lock().unwrap() = idx as u32 + 1;
-                error!("generate spend proof failed");
-                return vec![];
-            }
-            proof_bytes.unwrap()
-        })
-        .collect();
+    let encoding = match ProofEncoding::from_content_type(&headers) {
+        Ok(encoding) => encoding,
+        Err(e) => return e.into_response(),
+    };
+    let request = match encoding.decode_request(&body) {
+        Ok(request) => request,
+        Err(e) => return e.into_response(),
+    };
+    let result = prove_cached(
+        &prover.pool,
+        prover.cache.as_ref(),
+        request,
+        prover.self_check,
+        Admission::Try,
+        None,
+    )
+    .await;
+    match result {
+        Ok(proof) => ProofEncoding::from_accept(&headers, encoding).encode_response(&proof),
+        Err(e) => e.into_response(),
+    }
+}
 
-    let idx = *failed_index.lock().unwrap();
-    if idx > 0 {
-        return OreoError::GenerateSpendProofFailed(idx - 1).into_response();
+pub async fn stream_proof_handler(
+    State(prover): State<ProverState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> impl IntoResponse {
+    info!("calling stream_proof_handler");
+    let encoding = match ProofEncoding::from_content_type(&headers) {
+        Ok(encoding) => encoding,
+        Err(e) => return e.into_response(),
+    };
+    let request = match encoding.decode_request(&body) {
+        Ok(request) => request,
+        Err(e) => return e.into_response(),
+    };
+    // refuse requests which can't be proven now before opening the stream
+    let reservation = match prover.pool.reserve(request.circuits()) {
+        Ok(reservation) => reservation,
+        Err(e) => return e.into_response(),
+    };
+    let encoding = ProofEncoding::from_accept(&headers, encoding);
+    Sse::new(stream_proofs(prover, request, reservation, encoding))
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+pub async fn coordinate_proof_handler(
+    State(coordinator): State<Coordinator>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> impl IntoResponse {
+    info!("calling coordinate_proof_handler");
+    let encoding = match ProofEncoding::from_content_type(&headers) {
+        Ok(encoding) => encoding,
+        Err(e) => return e.into_response(),
+    };
+    let request = match encoding.decode_request(&body) {
+        Ok(request) => request,
+        Err(e) => return e.into_response(),
+    };
+    match coordinator.prove(&request).await {
+        Ok(proof) => ProofEncoding::from_accept(&headers, encoding).encode_response(&proof),
+        Err(e) => e.into_response(),
     }
+}
 
-    let output_proofs = request
-        .output_circuits
-        .par_iter()
-        .enumerate()
-        .map(|(idx, bytes)| {
-            let proof_bytes = if let Ok(output) = Output::read(&bytes[..]) {
-                let proof =
-                    groth16::create_random_proof(output, &SAPLING.output_params, &mut thread_rng());
-                if let Ok(proof) = proof {
-                    let mut writer = vec![];
-                    proof.write(&mut writer).unwrap();
-                    Some(writer)
-                } else {
-                    None
-                }
-            } else {
-                None
-            };
-            if proof_bytes.is_none() {
-                *failed_index.lock().unwrap() = idx as u32 + 1;
-                error!("generate output proof failed");
-                return vec![];
-            }
-            proof_bytes.unwrap()
-        })
-        .collect();
+pub async fn register_worker_handler(
+    State(coordinator): State<Coordinator>,
+    headers: HeaderMap,
+    extract::Json(request): extract::Json<RegisterWorkerReq>,
+) -> impl IntoResponse {
+    match coordinator.register(&headers, &request.url) {
+        Ok(registered) => {
+            Json(json!({"code": 200, "data": {"registered": registered}})).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn get_workers_handler(State(coordinator): State<Coordinator>) -> impl IntoResponse {
+    Json(json!({"code": 200, "data": {"workers": coordinator.list()}})).into_response()
+}
+
+pub async fn metrics_handler(State(prover): State<ProverState>) -> impl IntoResponse {
+    let metrics = metrics();
+    metrics.jobs_queued.set(prover.jobs.queued() as i64);
+    metrics
+        .circuits_in_flight
+        .set(prover.pool.in_flight() as i64);
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics.render(),
+    )
+        .into_response()
+}
+
+pub async fn ready_handler(State(prover): State<ProverState>) -> impl IntoResponse {
+    if prover.ready.load(Ordering::Acquire) {
+        Json(json!({"code": 200, "data": {"ready": true}})).into_response()
+    } else {
+        OreoError::ProverNotReady.into_response()
+    }
+}
+
+/// Route middleware refusing proof requests until the params are loaded, a request proving
+/// meanwhile would initialize the builtin params under the loader
+pub async fn require_ready(
+    State(prover): State<ProverState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !prover.ready.load(Ordering::Acquire) {
+        return OreoError::ProverNotReady.into_response();
+    }
+    next.run(request).await
+}
+
+pub async fn submit_proof_job_handler(
+    State(prover): State<ProverState>,
+    permit: Option<Extension<ClientPermit>>,
+    extract::Json(request): extract::Json<GenerateProofReq>,
+) -> impl IntoResponse {
+    match prover.jobs.submit(request, permit.map(|Extension(x)| x)) {
+        Ok(id) => Json(json!({"code": 200, "data": {"id": id}})).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn get_proof_job_handler(
+    State(prover): State<ProverState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match prover.jobs.get(&id) {
+        Ok(job) => Json(json!({"code": 200, "data": job})).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
 
-    let idx = *failed_index.lock().unwrap();
-    if idx > 0 {
-        return OreoError::GenerateSpendProofFailed(idx - 1).into_response();
+pub async fn verify_proof_handler(
+    State(prover): State<ProverState>,
+    extract::Json(request): extract::Json<VerifyProofReq>,
+) -> impl IntoResponse {
+    info!("calling verify_proof_handler");
+    let result = prover
+        .pool
+        .try_run(request.proofs(), move || verify_proofs(&request))
+        .await
+        .and_then(|x| x);
+    match result {
+        Ok(result) => Json(json!({"code": 200, "data": result})).into_response(),
+        Err(e) => e.into_response(),
     }
+}
+
+/// Verify every proof of `request` against the prepared verifying key of its circuit,
+/// malformed proofs or inputs fail the whole request
+pub fn verify_proofs(request: &VerifyProofReq) -> Result<VerifyProofRep, OreoError> {
+    let spends = read_proofs(CircuitKind::Spend, &request.spend_proofs)?;
+    let outputs = read_proofs(CircuitKind::Output, &request.output_proofs)?;
+    let mints = read_proofs(CircuitKind::Mint, &request.mint_asset_proofs)?;
+    let spend_proofs = check_proofs(spends, || prover_params().spend_verifying_key);
+    let output_proofs = check_proofs(outputs, || prover_params().output_verifying_key);
+    let mint_asset_proofs = check_proofs(mints, || prover_params().mint_verifying_key);
+    Ok(VerifyProofRep {
+        valid: spend_proofs
+            .iter()
+            .chain(output_proofs.iter())
+            .chain(mint_asset_proofs.iter())
+            .all(|x| *x),
+        spend_proofs,
+        output_proofs,
+        mint_asset_proofs,
+    })
+}
 
-    let mint_asset_proofs = request
-        .mint_asset_circuits
+type ParsedProof = (groth16::Proof<Bls12>, Vec<Scalar>);
+
+fn read_proofs(
+    kind: CircuitKind,
+    proofs: &[ProofWithInputs],
+) -> Result<Vec<ParsedProof>, OreoError> {
+    let malformed = |index: usize, reason: &str| {
+        OreoError::InvalidProofRequest(format!("{} proof `{}` {}", kind, index, reason))
+    };
+    let mut parsed = vec![];
+    for (index, item) in proofs.iter().enumerate() {
+        let proof = groth16::Proof::<Bls12>::read(&item.proof[..])
+            .map_err(|e| malformed(index, &e.to_string()))?;
+        let mut inputs = vec![];
+        for input in item.public_inputs.iter() {
+            let bytes: [u8; 32] = input
+                .as_slice()
+                .try_into()
+                .map_err(|_| malformed(index, "has a public input not of 32 bytes"))?;
+            let scalar: Option<Scalar> = Scalar::from_bytes_le(&bytes).into();
+            inputs.push(scalar.ok_or(malformed(index, "has a non canonical public input"))?);
+        }
+        parsed.push((proof, inputs));
+    }
+    Ok(parsed)
+}
+
+fn check_proofs(
+    proofs: Vec<ParsedProof>,
+    key: impl FnOnce() -> &'static groth16::PreparedVerifyingKey<Bls12>,
+) -> Vec<bool> {
+    if proofs.is_empty() {
+        return vec![];
+    }
+    let key = key();
+    proofs
         .par_iter()
-        .enumerate()
-        .map(|(idx, bytes)| {
-            let proof_bytes = if let Ok(mint_asset) = MintAsset::read(&bytes[..]) {
-                let proof = groth16::create_random_proof(
-                    mint_asset,
-                    &SAPLING.mint_params,
-                    &mut thread_rng(),
-                );
-                if let Ok(proof) = proof {
-                    let mut writer = vec![];
-                    proof.write(&mut writer).unwrap();
-                    Some(writer)
-                } else {
-                    None
+        .map(|(proof, inputs)| groth16::verify_proof(key, proof, inputs).unwrap_or(false))
+        .collect()
+}
+
+/// Called with the kind, index and bytes of each proof as soon as it is ready
+pub type ProofSink = Arc<dyn Fn(CircuitKind, usize, &[u8]) + Send + Sync>;
+
+/// Generate all proofs of `request`, circuits are all read before proving and every
+/// failing circuit is reported, with `check` proofs are verified before being returned.
+/// Proofs are passed to `on_proof` as they complete, once verified with `check`
+pub fn generate_proofs(
+    request: &GenerateProofReq,
+    check: bool,
+    on_proof: Option<&ProofSink>,
+) -> Result<GenerateProofRep, OreoError> {
+    metrics().batch_circuits.observe(request.circuits() as f64);
+    let result = prove_request(request, check, on_proof);
+    if let Some(failures) = result.as_ref().err().and_then(|e| e.failures()) {
+        metrics().observe_failures(failures);
+    }
+    result
+}
+
+fn prove_request(
+    request: &GenerateProofReq,
+    check: bool,
+    on_proof: Option<&ProofSink>,
+) -> Result<GenerateProofRep, OreoError> {
+    let (spends, mut failures) = read_circuits(CircuitKind::Spend, &request.spend_circuits, |x| {
+        Spend::read(x)
+    });
+    let (outputs, output_failures) =
+        read_circuits(CircuitKind::Output, &request.output_circuits, |x| {
+            Output::read(x)
+        });
+    let (mints, mint_failures) =
+        read_circuits(CircuitKind::Mint, &request.mint_asset_circuits, |x| {
+            MintAsset::read(x)
+        });
+    failures.extend(output_failures);
+    failures.extend(mint_failures);
+    if !failures.is_empty() {
+        return Err(OreoError::proof_failed(failures));
+    }
+
+    // checked proofs are only passed on once they all verified
+    let sink = on_proof.filter(|_| !check);
+    let (spend_proofs, mut failures) = prove_circuits(CircuitKind::Spend, spends, sink, || {
+        prover_params().spend_params
+    });
+    let (output_proofs, output_failures) =
+        prove_circuits(CircuitKind::Output, outputs, sink, || {
+            prover_params().output_params
+        });
+    let (mint_asset_proofs, mint_failures) = prove_circuits(CircuitKind::Mint, mints, sink, || {
+        prover_params().mint_params
+    });
+    failures.extend(output_failures);
+    failures.extend(mint_failures);
+    if !failures.is_empty() {
+        return Err(OreoError::proof_failed(failures));
+    }
+
+    if check {
+        let mut failures = self_check(
+            CircuitKind::Spend,
+            &request.spend_circuits,
+            &spend_proofs,
+            |x| Spend::read(x),
+            || prover_params().spend_verifying_key,
+        );
+        failures.extend(self_check(
+            CircuitKind::Output,
+            &request.output_circuits,
+            &output_proofs,
+            |x| Output::read(x),
+            || prover_params().output_verifying_key,
+        ));
+        failures.extend(self_check(
+            CircuitKind::Mint,
+            &request.mint_asset_circuits,
+            &mint_asset_proofs,
+            |x| MintAsset::read(x),
+            || prover_params().mint_verifying_key,
+        ));
+        if !failures.is_empty() {
+            return Err(OreoError::proof_failed(failures));
+        }
+        if let Some(on_proof) = on_proof {
+            for (kind, proofs) in [
+                (CircuitKind::Spend, &spend_proofs),
+                (CircuitKind::Output, &output_proofs),
+                (CircuitKind::Mint, &mint_asset_proofs),
+            ] {
+                for (index, proof) in proofs.iter().enumerate() {
+                    on_proof(kind, index, proof);
                 }
-            } else {
-                None
-            };
-            if proof_bytes.is_none() {
-                *failed_index.lock().unwrap() = idx as u32 + 1;
-                error!("generate mint asset proof failed");
-                return vec![];
             }
-            proof_bytes.unwrap()
-        })
-        .collect();
-
-    let idx = *failed_index.lock().unwrap();
-    if idx > 0 {
-        return OreoError::GenerateMintAssetProofFailed(idx - 1).into_response();
+        }
     }
 
-    let proof = GenerateProofRep {
+    Ok(GenerateProofRep {
         spend_proofs,
         output_proofs,
         mint_asset_proofs,
-    };
+    })
+}
 
-    Json(json!({"code": 200, "data": proof})).into_response()
+fn read_circuits<C>(
+    kind: CircuitKind,
+    circuits: &[Vec<u8>],
+    read: impl Fn(&[u8]) -> std::io::Result<C>,
+) -> (Vec<C>, Vec<CircuitFailure>) {
+    let mut parsed = vec![];
+    let mut failures = vec![];
+    for (index, bytes) in circuits.iter().enumerate() {
+        match read(&bytes[..]) {
+            Ok(circuit) => parsed.push(circuit),
+            Err(e) => failures.push(CircuitFailure {
+                kind,
+                index: index as u32,
+                reason: FailureReason::Deserialize,
+                message: e.to_string(),
+            }),
+        }
+    }
+    (parsed, failures)
+}
+
+fn prove_circuits<C: Circuit<Scalar> + Send>(
+    kind: CircuitKind,
+    circuits: Vec<C>,
+    on_proof: Option<&ProofSink>,
+    params: impl FnOnce() -> &'static groth16::Parameters<Bls12>,
+) -> (Vec<Vec<u8>>, Vec<CircuitFailure>) {
+    if circuits.is_empty() {
+        return (vec![], vec![]);
+    }
+    // params are loaded on first use
+    let params = params();
+    let results: Vec<Result<Vec<u8>, CircuitFailure>> = circuits
+        .into_par_iter()
+        .enumerate()
+        .map(|(index, circuit)| {
+            let failure = |message: String| CircuitFailure {
+                kind,
+                index: index as u32,
+                reason: FailureReason::Synthesis,
+                message,
+            };
+            let start = Instant::now();
+            let proof = groth16::create_random_proof(circuit, params, &mut thread_rng())
+                .map_err(|e| failure(e.to_string()))?;
+            metrics().observe_proof(kind, start.elapsed().as_secs_f64());
+            let mut writer = vec![];
+            proof
+                .write(&mut writer)
+                .map_err(|e| failure(e.to_string()))?;
+            if let Some(on_proof) = on_proof {
+                on_proof(kind, index, &writer);
+            }
+            Ok(writer)
+        })
+        .collect();
+    let mut proofs = vec![];
+    let mut failures = vec![];
+    for result in results.into_iter() {
+        match result {
+            Ok(proof) => proofs.push(proof),
+            Err(failure) => {
+                error!("generate {} proof {} failed", failure.kind, failure.index);
+                failures.push(failure);
+            }
+        }
+    }
+    (proofs, failures)
 }